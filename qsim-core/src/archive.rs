@@ -0,0 +1,485 @@
+//! Compact storage for ψ(x, t) over long runs
+//!
+//! An archive starts with `MAGIC`, the format version as a little endian `u32`
+//! and a JSON `ArchiveHeader` prefixed by its length as a `u32`. Chunks follow,
+//! each holding up to `frames_per_chunk` frames:
+//!
+//! - the number of frames as a `u32` and their times as `f64`s
+//! - the length of the compressed data as a `u64`
+//! - the real and imaginary parts of every point, frame after frame, as `f64`s
+//!   with their bytes shuffled (all first bytes, then all second bytes, ...)
+//!   before deflating, which compresses smooth data much better
+//!
+//! Chunks are only ever appended, so an archive can be read while it is still
+//! being written, and a run that stops halfway loses at most its last chunk
+
+use crate::complex::Complex;
+use crate::experiment::Experiment;
+use crate::export::UNITS;
+use crate::linear::Vector;
+use crate::project::Project;
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    ops::Range,
+    path::Path,
+};
+
+pub const MAGIC: &[u8; 8] = b"QSIMARC\0";
+pub const ARCHIVE_VERSION: u32 = 1;
+/// Deflate expands data at most about 1032 times, which bounds what a chunk can decompress to
+const MAX_DEFLATE_RATIO: u64 = 1032;
+
+/// Everything needed to make sense of the frames and to reproduce them
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchiveHeader {
+    /// Grid, solver settings, momentum and the formulas or drawings the run started from
+    pub project: Project,
+    pub units: BTreeMap<String, String>,
+    pub frames_per_chunk: u32,
+}
+impl ArchiveHeader {
+    pub fn new(project: Project, frames_per_chunk: u32) -> Result<Self, String> {
+        if frames_per_chunk == 0 {
+            return Err("Chunks need room for at least one frame".into());
+        }
+        let units = UNITS.iter().map(|(quantity, unit)| (quantity.to_string(), unit.to_string())).collect();
+        Ok(Self { project, units, frames_per_chunk })
+    }
+
+    /// Points per frame
+    pub fn resolution(&self) -> usize {
+        self.project.grid.resolution() as usize
+    }
+}
+
+/// Appends frames to an archive, compressing them a chunk at a time
+pub struct ArchiveWriter<W: Write> {
+    writer: W,
+    header: ArchiveHeader,
+    times: Vec<f64>,
+    values: Vec<f64>,
+    frames: usize,
+}
+impl ArchiveWriter<BufWriter<fs::File>> {
+    pub fn create(path: &Path, header: ArchiveHeader) -> Result<Self, String> {
+        let file = fs::File::create(path).map_err(|error| format!("{}: {}", path.display(), error))?;
+        Self::new(BufWriter::new(file), header)
+    }
+}
+impl<W: Write> ArchiveWriter<W> {
+    pub fn new(mut writer: W, header: ArchiveHeader) -> Result<Self, String> {
+        // The fields are public, so the check in `ArchiveHeader::new` may have been skipped
+        if header.frames_per_chunk == 0 {
+            return Err("Chunks need room for at least one frame".into());
+        }
+        let json = serde_json::to_vec(&header).expect("headers always serialize");
+        let write = |writer: &mut W| -> io::Result<()> {
+            writer.write_all(MAGIC)?;
+            writer.write_all(&ARCHIVE_VERSION.to_le_bytes())?;
+            writer.write_all(&(json.len() as u32).to_le_bytes())?;
+            writer.write_all(&json)
+        };
+        write(&mut writer).map_err(|error| error.to_string())?;
+        Ok(Self { writer, header, times: vec![], values: vec![], frames: 0 })
+    }
+
+    pub fn header(&self) -> &ArchiveHeader {
+        &self.header
+    }
+
+    /// Frames pushed so far, including those not yet written out
+    pub fn frame_count(&self) -> usize {
+        self.frames
+    }
+
+    pub fn push(&mut self, time: f64, frame: &Vector) -> Result<(), String> {
+        if frame.len() != self.header.resolution() {
+            return Err(format!(
+                "Frame has {} points but the grid has {}",
+                frame.len(),
+                self.header.resolution()
+            ));
+        }
+        self.times.push(time);
+        self.values.extend(frame.iter().flat_map(|value| [value.real, value.imag]));
+        self.frames += 1;
+        if self.times.len() == self.header.frames_per_chunk as usize {
+            self.write_chunk()?;
+        }
+        Ok(())
+    }
+
+    /// Evolves the experiment to every time in `times` and appends the frames,
+    /// one chunk at a time so long runs never hold more than a chunk in memory
+    pub fn record(&mut self, experiment: &Experiment, times: &[f64]) -> Result<(), String> {
+        for times in times.chunks(self.header.frames_per_chunk as usize) {
+            for (time, frame) in times.iter().zip(experiment.evolve_many(times)?) {
+                self.push(*time, &frame)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the frames still pending and hands back the underlying writer
+    pub fn finish(mut self) -> Result<W, String> {
+        self.write_chunk()?;
+        self.writer.flush().map_err(|error| error.to_string())?;
+        Ok(self.writer)
+    }
+
+    fn write_chunk(&mut self) -> Result<(), String> {
+        if self.times.is_empty() {
+            return Ok(());
+        }
+        let mut encoder = DeflateEncoder::new(vec![], Compression::default());
+        let compressed = encoder.write_all(&shuffle(&self.values)).and_then(|_| encoder.finish());
+        let write = |writer: &mut W, compressed: Vec<u8>| -> io::Result<()> {
+            writer.write_all(&(self.times.len() as u32).to_le_bytes())?;
+            for time in &self.times {
+                writer.write_all(&time.to_le_bytes())?;
+            }
+            writer.write_all(&(compressed.len() as u64).to_le_bytes())?;
+            writer.write_all(&compressed)?;
+            // Makes the chunk visible to readers right away
+            writer.flush()
+        };
+        compressed
+            .and_then(|compressed| write(&mut self.writer, compressed))
+            .map_err(|error| error.to_string())?;
+        self.times.clear();
+        self.values.clear();
+        Ok(())
+    }
+}
+
+/// Where a chunk's frames live in the archive
+#[derive(Debug, Clone)]
+struct Chunk {
+    first: usize,
+    times: Vec<f64>,
+    offset: u64,
+    length: u64,
+}
+
+/// Reads frames by index without decompressing more than the chunks they are in
+pub struct ArchiveReader<R: Read + Seek> {
+    reader: R,
+    header: ArchiveHeader,
+    chunks: Vec<Chunk>,
+    frames: usize,
+}
+impl ArchiveReader<BufReader<fs::File>> {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let file = fs::File::open(path).map_err(|error| format!("{}: {}", path.display(), error))?;
+        Self::new(BufReader::new(file)).map_err(|error| format!("{}: {}", path.display(), error))
+    }
+}
+impl<R: Read + Seek> ArchiveReader<R> {
+    /// Reads the header and indexes the chunks. A chunk cut short, as left by a
+    /// run that is still going or was interrupted, is ignored
+    pub fn new(mut reader: R) -> Result<Self, String> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic).map_err(|_| "Not a qsim archive")?;
+        if &magic != MAGIC {
+            return Err("Not a qsim archive".into());
+        }
+        let version = read_u32(&mut reader).map_err(|_| "Truncated archive header")?;
+        if version != ARCHIVE_VERSION {
+            return Err(format!("Archive version {} is not supported, only {}", version, ARCHIVE_VERSION));
+        }
+        let length = read_u32(&mut reader).map_err(|_| "Truncated archive header")?;
+        let end = reader.seek(SeekFrom::End(0)).map_err(|error| error.to_string())?;
+        // Magic, version and header length take 16 bytes
+        if 16 + length as u64 > end {
+            return Err("Truncated archive header".into());
+        }
+        reader.seek(SeekFrom::Start(16)).map_err(|error| error.to_string())?;
+        let mut json = vec![0; length as usize];
+        reader.read_exact(&mut json).map_err(|_| "Truncated archive header")?;
+        let header: ArchiveHeader =
+            serde_json::from_slice(&json).map_err(|error| format!("Invalid archive header: {}", error))?;
+
+        let mut position = 16 + length as u64;
+        let mut chunks = vec![];
+        let mut frames = 0;
+        while let Ok(chunk) = read_chunk_index(&mut reader, position, end, frames, &header) {
+            frames += chunk.times.len();
+            position = chunk.offset + chunk.length;
+            chunks.push(chunk);
+        }
+
+        Ok(Self { reader, header, chunks, frames })
+    }
+
+    pub fn header(&self) -> &ArchiveHeader {
+        &self.header
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames
+    }
+
+    /// Times of every frame, without decompressing any of them
+    pub fn times(&self) -> Vec<f64> {
+        self.chunks.iter().flat_map(|chunk| chunk.times.iter().copied()).collect()
+    }
+
+    /// The frames with indices in `range`, with their times
+    pub fn read_frames(&mut self, range: Range<usize>) -> Result<Vec<(f64, Vector)>, String> {
+        if range.start > range.end || range.end > self.frames {
+            return Err(format!(
+                "Frames {}..{} are out of range, the archive has {}",
+                range.start, range.end, self.frames
+            ));
+        }
+        let resolution = self.header.resolution();
+        let mut frames = Vec::with_capacity(range.len());
+        for chunk in self.chunks.clone() {
+            let chunk_range = chunk.first..chunk.first + chunk.times.len();
+            if chunk_range.end <= range.start || chunk_range.start >= range.end {
+                continue;
+            }
+
+            let values = self.read_chunk(&chunk)?;
+            for index in range.start.max(chunk_range.start)..range.end.min(chunk_range.end) {
+                let frame = index - chunk.first;
+                let values = &values[2 * frame * resolution..2 * (frame + 1) * resolution];
+                let points = values.chunks(2).map(|value| Complex::new(value[0], value[1])).collect::<Vec<Complex>>();
+                frames.push((chunk.times[frame], Vector::from(points)));
+            }
+        }
+        Ok(frames)
+    }
+
+    pub fn read_frame(&mut self, index: usize) -> Result<(f64, Vector), String> {
+        if index >= self.frames {
+            return Err(format!("Frame {} is out of range, the archive has {}", index, self.frames));
+        }
+        Ok(self.read_frames(index..index + 1)?.remove(0))
+    }
+
+    fn read_chunk(&mut self, chunk: &Chunk) -> Result<Vec<f64>, String> {
+        let corrupt = || format!("Chunk at byte {} is corrupt", chunk.offset);
+        // The size comes from the header, so it is checked against what the chunk's bytes could hold before allocating
+        let expected = chunk
+            .times
+            .len()
+            .checked_mul(self.header.resolution())
+            .and_then(|points| points.checked_mul(2 * 8))
+            .filter(|&expected| expected as u64 <= chunk.length.saturating_mul(MAX_DEFLATE_RATIO))
+            .ok_or_else(corrupt)?;
+        let mut compressed = vec![0; chunk.length as usize];
+        self.reader.seek(SeekFrom::Start(chunk.offset)).map_err(|error| error.to_string())?;
+        self.reader.read_exact(&mut compressed).map_err(|error| error.to_string())?;
+
+        let mut bytes = Vec::with_capacity(expected);
+        // Reading one byte past the expected size is enough to tell that the chunk holds too much
+        DeflateDecoder::new(&compressed[..])
+            .take(expected as u64 + 1)
+            .read_to_end(&mut bytes)
+            .map_err(|_| corrupt())?;
+        if bytes.len() != expected {
+            return Err(corrupt());
+        }
+        Ok(unshuffle(&bytes))
+    }
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+/// Reads the chunk header at `position`, failing if the chunk doesn't fit before `end`
+fn read_chunk_index(
+    reader: &mut (impl Read + Seek),
+    position: u64,
+    end: u64,
+    first: usize,
+    header: &ArchiveHeader,
+) -> io::Result<Chunk> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "Incomplete chunk");
+    reader.seek(SeekFrom::Start(position))?;
+    let count = read_u32(reader)?;
+    if count == 0 || count > header.frames_per_chunk || position + 4 + 8 * count as u64 + 8 > end {
+        return Err(invalid());
+    }
+    let times = (0..count)
+        .map(|_| read_u64(reader).map(f64::from_bits))
+        .collect::<io::Result<Vec<f64>>>()?;
+    let length = read_u64(reader)?;
+    let offset = position + 4 + 8 * count as u64 + 8;
+    if length > end - offset {
+        return Err(invalid());
+    }
+    Ok(Chunk { first, times, offset, length })
+}
+
+/// Groups the bytes of the values by significance, HDF5's shuffle filter
+fn shuffle(values: &[f64]) -> Vec<u8> {
+    let mut bytes = vec![0; values.len() * 8];
+    for (i, value) in values.iter().enumerate() {
+        for (k, byte) in value.to_le_bytes().into_iter().enumerate() {
+            bytes[k * values.len() + i] = byte;
+        }
+    }
+    bytes
+}
+
+fn unshuffle(bytes: &[u8]) -> Vec<f64> {
+    let count = bytes.len() / 8;
+    (0..count)
+        .map(|i| {
+            let mut value = [0; 8];
+            for (k, byte) in value.iter_mut().enumerate() {
+                *byte = bytes[k * count + i];
+            }
+            f64::from_le_bytes(value)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::experiment::{Grid, Solver};
+    use crate::import::Interpolation;
+    use crate::project::FunctionSource;
+    use std::io::Cursor;
+
+    const RESOLUTION: u32 = 16;
+
+    fn header(frames_per_chunk: u32) -> ArchiveHeader {
+        let source = |formula: &str| FunctionSource {
+            formula: formula.into(),
+            datapoints: vec![],
+            use_formula: true,
+            interpolation: Interpolation::default(),
+        };
+        let project = Project {
+            potential: source("x^2"),
+            wavefunction: source("exp(-x^2)"),
+            grid: Grid::new(-4.0, 4.0, RESOLUTION).unwrap(),
+            momentum: 0.0,
+            solver: Solver::default(),
+        };
+        ArchiveHeader::new(project, frames_per_chunk).unwrap()
+    }
+
+    /// Frame `k` holds `k + i j (k + 1)` at point `j`
+    fn frame(k: usize) -> Vector {
+        Vector::from((0..RESOLUTION as usize).map(|j| Complex::new(k as f64, (j * (k + 1)) as f64)).collect::<Vec<Complex>>())
+    }
+
+    /// An archive of `count` frames at times `k / 4`
+    fn archive(frames_per_chunk: u32, count: usize) -> ArchiveReader<Cursor<Vec<u8>>> {
+        let mut writer = ArchiveWriter::new(vec![], header(frames_per_chunk)).unwrap();
+        for k in 0..count {
+            writer.push(k as f64 / 4.0, &frame(k)).unwrap();
+        }
+        ArchiveReader::new(Cursor::new(writer.finish().unwrap())).unwrap()
+    }
+
+    #[test]
+    fn frames_round_trip_by_range() {
+        // 10 frames in chunks of 4 leave a partial last chunk of 2
+        let mut reader = archive(4, 10);
+        assert_eq!(reader.header(), &header(4));
+        assert_eq!(reader.frame_count(), 10);
+        assert_eq!(reader.times(), (0..10).map(|k| k as f64 / 4.0).collect::<Vec<f64>>());
+
+        for range in [0..10, 3..9, 8..10, 9..10, 5..5] {
+            let frames = reader.read_frames(range.clone()).unwrap();
+            assert_eq!(frames.len(), range.len());
+            for ((time, vector), k) in frames.into_iter().zip(range) {
+                assert_eq!(time, k as f64 / 4.0);
+                assert_eq!(vector.to_vec(), frame(k).to_vec());
+            }
+        }
+        assert_eq!(reader.read_frame(9).unwrap().1.to_vec(), frame(9).to_vec());
+    }
+
+    #[test]
+    fn out_of_range_frames_are_errors() {
+        let mut reader = archive(4, 10);
+        assert!(reader.read_frame(10).is_err());
+        assert!(reader.read_frame(usize::MAX).is_err());
+        assert!(reader.read_frames(8..11).is_err());
+        assert!(archive(4, 0).read_frame(0).is_err());
+    }
+
+    #[test]
+    fn chunks_need_room_for_a_frame() {
+        assert!(ArchiveHeader::new(header(1).project, 0).is_err());
+        let mut header = header(1);
+        header.frames_per_chunk = 0;
+        assert!(ArchiveWriter::new(vec![], header).is_err());
+    }
+
+    #[test]
+    fn recorded_frames_match_the_evolution() {
+        let header = header(3);
+        let experiment = header.project.solve().unwrap();
+        let times = [0.0, 0.5, 1.0, 1.5, 2.0];
+        let mut writer = ArchiveWriter::new(vec![], header).unwrap();
+        writer.record(&experiment, &times).unwrap();
+        let mut reader = ArchiveReader::new(Cursor::new(writer.finish().unwrap())).unwrap();
+
+        assert_eq!(reader.times(), times);
+        for (k, (time, vector)) in reader.read_frames(0..times.len()).unwrap().into_iter().enumerate() {
+            assert_eq!(time, times[k]);
+            assert_eq!(vector.to_vec(), experiment.evolve(times[k]).unwrap().to_vec());
+        }
+    }
+
+    /// An archive with `header` and a single chunk of one frame at time 0 holding `values`
+    fn archive_with_chunk(header: ArchiveHeader, values: &[f64]) -> ArchiveReader<Cursor<Vec<u8>>> {
+        let mut bytes = ArchiveWriter::new(vec![], header).unwrap().finish().unwrap();
+        let mut encoder = DeflateEncoder::new(vec![], Compression::default());
+        encoder.write_all(&shuffle(values)).unwrap();
+        let compressed = encoder.finish().unwrap();
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        bytes.extend_from_slice(&0f64.to_le_bytes());
+        bytes.extend_from_slice(&(compressed.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&compressed);
+        ArchiveReader::new(Cursor::new(bytes)).unwrap()
+    }
+
+    #[test]
+    fn chunks_of_the_wrong_size_are_corrupt() {
+        let frame = vec![1.0; 2 * RESOLUTION as usize];
+        let (_, vector) = archive_with_chunk(header(1), &frame).read_frame(0).unwrap();
+        assert_eq!(vector.to_vec(), vec![Complex::new(1.0, 1.0); RESOLUTION as usize]);
+        assert!(archive_with_chunk(header(1), &frame[1..]).read_frame(0).is_err());
+        assert!(archive_with_chunk(header(1), &vec![0.0; 1 << 20]).read_frame(0).is_err());
+
+        // A header claiming huge frames must not make the reader allocate for them
+        let mut huge = header(1);
+        huge.project.grid = Grid::new(-4.0, 4.0, u32::MAX).unwrap();
+        assert!(archive_with_chunk(huge, &frame).read_frame(0).is_err());
+    }
+
+    #[test]
+    fn truncated_last_chunk_is_ignored() {
+        let mut writer = ArchiveWriter::new(vec![], header(4)).unwrap();
+        for k in 0..6 {
+            writer.push(k as f64, &frame(k)).unwrap();
+        }
+        let mut bytes = writer.finish().unwrap();
+        bytes.truncate(bytes.len() - 1);
+        let mut reader = ArchiveReader::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(reader.frame_count(), 4);
+        assert_eq!(reader.read_frame(3).unwrap().1.to_vec(), frame(3).to_vec());
+    }
+}
@@ -0,0 +1,142 @@
+//! Runs an experiment without the GUI and exports its results
+//!
+//! Usage: `qsim [--format csv|npy|npz|jsonl] [--archive <run.qsa>] [--animation <out.gif|directory>]
+//! [--plots <directory>] <experiment.json> <output directory>`.
+//! The experiment is either a project file saved by the app or a short description like
+//! `{"potential": "x^2", "wavefunction": "exp(-x^2)", "grid": {"start": -5, "end": 5,
+//! "resolution": 200}, "momentum": 1}`, where `momentum` and the `solver` settings
+//! are optional. Either may list the `times` to write frames for and the
+//! `eigenvectors` to write, all of them by default. With `--archive`, the frames
+//! are also recorded into a compressed archive as they are computed. With
+//! `--animation`, they are drawn into a GIF, or numbered PNGs when the path is not
+//! a `.gif`, using the optional `render` settings of the description. With
+//! `--plots`, SVG figures of the potential, the eigenstates and the density at
+//! those times are written too
+
+use qsim_core::{
+    archive::{ArchiveHeader, ArchiveWriter},
+    export::{self, Format},
+    plot,
+    render::{self, RenderSettings},
+    Project,
+};
+use serde_json::Value;
+use std::{fs, path::Path, process};
+
+const USAGE: &str = "usage: qsim [--format csv|npy|npz|jsonl] [--archive <run.qsa>] \
+                     [--animation <out.gif|directory>] [--plots <directory>] <experiment.json> <output directory>";
+/// Eigenstates plotted when the description doesn't list any
+const PLOTTED_EIGENSTATES: usize = 5;
+/// Frames per archive chunk
+const FRAMES_PER_CHUNK: u32 = 64;
+
+fn main() {
+    let mut arguments: Vec<String> = std::env::args().skip(1).collect();
+    if arguments.iter().any(|argument| argument == "--help" || argument == "-h") {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    }
+
+    let format = match option(&mut arguments, "--format").map(|format| Format::parse(&format)) {
+        None => Format::Csv,
+        Some(Ok(format)) => format,
+        Some(Err(message)) => {
+            eprintln!("qsim: {}", message);
+            process::exit(2);
+        }
+    };
+    let archive = option(&mut arguments, "--archive");
+    let animation = option(&mut arguments, "--animation");
+    let plots = option(&mut arguments, "--plots");
+    if arguments.len() != 2 {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    }
+
+    let outputs = Outputs {
+        directory: Path::new(&arguments[1]),
+        format,
+        archive: archive.as_deref().map(Path::new),
+        animation: animation.as_deref().map(Path::new),
+        plots: plots.as_deref().map(Path::new),
+    };
+    if let Err(message) = run(Path::new(&arguments[0]), &outputs) {
+        eprintln!("qsim: {}", message);
+        process::exit(1);
+    }
+}
+
+/// Removes `name` and the value after it from the arguments, exiting if the value is missing
+fn option(arguments: &mut Vec<String>, name: &str) -> Option<String> {
+    let position = arguments.iter().position(|argument| argument == name)?;
+    if position + 1 >= arguments.len() {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    }
+    let value = arguments.remove(position + 1);
+    arguments.remove(position);
+    Some(value)
+}
+
+/// Where results go and in which form
+struct Outputs<'a> {
+    directory: &'a Path,
+    format: Format,
+    archive: Option<&'a Path>,
+    animation: Option<&'a Path>,
+    plots: Option<&'a Path>,
+}
+
+fn run(input: &Path, outputs: &Outputs) -> Result<(), String> {
+    let description = fs::read_to_string(input).map_err(|error| format!("{}: {}", input.display(), error))?;
+    let description: Value =
+        serde_json::from_str(&description).map_err(|error| format!("{}: {}", input.display(), error))?;
+    let times: Vec<f64> = match description.get("times") {
+        Some(times) => serde_json::from_value(times.clone()).map_err(|error| format!("times: {}", error))?,
+        None => vec![],
+    };
+    let eigenvectors: Option<Vec<usize>> = match description.get("eigenvectors") {
+        Some(eigenvectors) => {
+            Some(serde_json::from_value(eigenvectors.clone()).map_err(|error| format!("eigenvectors: {}", error))?)
+        }
+        None => None,
+    };
+    let settings: RenderSettings = match description.get("render") {
+        Some(settings) => serde_json::from_value(settings.clone()).map_err(|error| format!("render: {}", error))?,
+        None => RenderSettings::default(),
+    };
+    let project = Project::from_value(description).map_err(|error| format!("{}: {}", input.display(), error))?;
+    let experiment = project.solve()?;
+
+    let count = experiment.basis().count();
+    let plotted: Vec<usize> = match &eigenvectors {
+        Some(eigenvectors) => eigenvectors.clone(),
+        None => (0..count.min(PLOTTED_EIGENSTATES)).collect(),
+    };
+    let eigenvectors = eigenvectors.unwrap_or_else(|| (0..count).collect());
+    export::export(&experiment, outputs.directory, outputs.format, &eigenvectors, &times)?;
+
+    if let Some(path) = outputs.archive {
+        let mut writer = ArchiveWriter::create(path, ArchiveHeader::new(project, FRAMES_PER_CHUNK)?)?;
+        writer
+            .record(&experiment, &times)
+            .and_then(|_| writer.finish())
+            .map_err(|error| format!("{}: {}", path.display(), error))?;
+    }
+
+    match outputs.animation {
+        Some(path) if path.extension().map_or(false, |extension| extension == "gif") => {
+            render::write_gif(path, &experiment, &times, settings)?
+        }
+        Some(directory) => {
+            render::write_pngs(directory, &experiment, &times, settings)?;
+        }
+        None => {}
+    }
+
+    if let Some(directory) = outputs.plots {
+        let times = if times.is_empty() { vec![0.0] } else { times };
+        plot::save_figures(&experiment, directory, &plotted, &times)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,901 @@
+use std::{
+    fmt,
+    iter::{Product, Sum},
+    ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign},
+};
+use num_traits::{Float, NumCast};
+use serde::{Deserialize, Serialize};
+
+/// Complex number over `f64` by default, `Complex<f32>` is meant for IPC payloads
+#[derive(Copy, Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct Complex<T = f64> {
+    pub real: T,
+    pub imag: T,
+}
+impl<T: Float> Complex<T> {
+    pub fn zero() -> Self {
+        Self { real: T::zero(), imag: T::zero() }
+    }
+
+    pub fn iunit() -> Self {
+        Self { real: T::zero(), imag: T::one() }
+    }
+
+    pub fn new(real: T, imag: T) -> Self {
+        Self { real, imag }
+    }
+
+    /// Converts to another precision, `None` if a part does not fit
+    pub fn cast<U: Float>(&self) -> Option<Complex<U>> {
+        Some(Complex {
+            real: <U as NumCast>::from(self.real)?,
+            imag: <U as NumCast>::from(self.imag)?,
+        })
+    }
+
+    pub fn from_polar_radians(modulus: T, angle: T) -> Self {
+        Self {
+            real: modulus * angle.cos(),
+            imag: modulus * angle.sin()
+        }
+    }
+
+    pub fn times_i(&self) -> Self {
+        Self { real: -self.imag, imag: self.real }
+    }
+
+    pub fn modulus_squared(&self) -> T {
+        self.real * self.real + self.imag * self.imag
+    }
+
+    /// Uses `hypot`, which neither overflows nor underflows for extreme parts
+    pub fn modulus(&self) -> T {
+        self.real.hypot(self.imag)
+    }
+
+    pub fn conjugate(&self) -> Self {
+        Self {
+            real: self.real,
+            imag: -self.imag
+        }
+    }
+
+    pub fn inverse(&self) -> Self {
+        Self::from(T::one()) / *self
+    }
+
+    pub fn angle_radians(&self) -> T {
+        self.imag.atan2(self.real)
+    }
+
+    /// Principal square root, with non-negative real part.
+    /// The branch cut runs along the negative real axis, the sign of a zero imaginary part
+    /// picks the side, so `sqrt(-1 + 0i) = i` and `sqrt(-1 - 0i) = -i`.
+    pub fn sqrt(&self) -> Self {
+        if self.is_zero() {
+            return Self::new(T::zero(), self.imag);
+        }
+        let two = T::one() + T::one();
+        let four = two * two;
+        // |z| and |re| + |z| overflow near the largest floats, where sqrt(z) = 2 sqrt(z / 4) keeps them in range
+        let large = |part: T| part.is_finite() && part.abs() > T::max_value() / four;
+        if large(self.real) || large(self.imag) {
+            return (*self / four).sqrt() * two;
+        }
+        let t = ((self.real.abs() + self.modulus()) / two).sqrt();
+        if self.real >= T::zero() {
+            Self::new(t, self.imag / (two * t))
+        } else {
+            Self::new(self.imag.abs() / (two * t), t.copysign(self.imag))
+        }
+    }
+
+    /// Integer power by repeated squaring, exact for Gaussian integers
+    pub fn powi(&self, exponent: i32) -> Self {
+        let mut base = if exponent < 0 { self.inverse() } else { *self };
+        let mut exponent = exponent.unsigned_abs();
+        let mut result = Self::from(T::one());
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result *= base;
+            }
+            base = base * base;
+            exponent >>= 1;
+        }
+        result
+    }
+
+    /// Principal power `exp(exponent * ln(self))`, with the branch cut of `ln`.
+    /// A zero base gives zero for exponents with positive real part, infinity for
+    /// negative real part, one for a zero exponent, and NaN otherwise.
+    pub fn powf(&self, exponent: &Self) -> Self {
+        if self.is_zero() {
+            return if exponent.is_zero() {
+                Self::from(T::one())
+            } else if exponent.real > T::zero() {
+                Self::zero()
+            } else if exponent.real < T::zero() && exponent.imag == T::zero() {
+                Self::new(T::infinity(), T::zero())
+            } else {
+                Self::new(T::nan(), T::nan())
+            };
+        }
+        if exponent.imag == T::zero()
+            && exponent.real.fract() == T::zero()
+            && exponent.real.abs() <= T::from(64).unwrap()
+        {
+            return self.powi(exponent.real.to_i32().unwrap());
+        }
+        (*exponent * self.ln()).exp()
+    }
+
+    pub fn cos(&self) -> Self {
+        Self {
+            real: self.real.cos() * self.imag.cosh(),
+            imag: - self.real.sin() * self.imag.sinh()
+        }
+    }
+
+    pub fn sin(&self) -> Self {
+        Self {
+            real: self.real.sin() * self.imag.cosh(),
+            imag: self.real.cos() * self.imag.sinh()
+        }
+    }
+
+    /// Through `tanh`, which stays finite for large imaginary parts
+    pub fn tan(&self) -> Self {
+        -self.times_i().tanh().times_i()
+    }
+
+    pub fn sinh(&self) -> Self {
+        Self {
+            real: self.real.sinh() * self.imag.cos(),
+            imag: self.real.cosh() * self.imag.sin()
+        }
+    }
+
+    pub fn cosh(&self) -> Self {
+        Self {
+            real: self.real.cosh() * self.imag.cos(),
+            imag: self.real.sinh() * self.imag.sin()
+        }
+    }
+
+    pub fn tanh(&self) -> Self {
+        let two = T::one() + T::one();
+        // cosh(2x) overflows long before tanh stops being +-1 to working precision
+        if self.real.abs() > T::from(20).unwrap() {
+            let decay = (-two * self.real.abs()).exp();
+            return Self::new(
+                T::one().copysign(self.real),
+                two * two * self.imag.sin() * self.imag.cos() * decay,
+            );
+        }
+        let denominator = (two * self.real).cosh() + (two * self.imag).cos();
+        Self::new(
+            (two * self.real).sinh() / denominator,
+            (two * self.imag).sin() / denominator,
+        )
+    }
+
+    /// Principal inverse sine, cuts along the real axis outside [-1, 1]
+    pub fn asin(&self) -> Self {
+        -self.times_i().asinh().times_i()
+    }
+
+    /// Principal inverse cosine, `pi/2 - asin`, cuts along the real axis outside [-1, 1]
+    pub fn acos(&self) -> Self {
+        Self::from(T::from(std::f64::consts::FRAC_PI_2).unwrap()) - self.asin()
+    }
+
+    /// Principal inverse tangent, cuts along the imaginary axis outside [-i, i]
+    pub fn atan(&self) -> Self {
+        -self.times_i().atanh().times_i()
+    }
+
+    /// Principal inverse hyperbolic sine, cuts along the imaginary axis outside [-i, i]
+    pub fn asinh(&self) -> Self {
+        // Odd symmetry keeps z + sqrt(z^2 + 1) away from cancellation
+        if self.real < T::zero() {
+            return -(-*self).asinh();
+        }
+        // ln(z + sqrt(z^2 + 1)) rewritten as ln_1p, so small arguments keep their precision
+        let square = *self * *self;
+        (*self + square / ((square + T::one()).sqrt() + T::one())).ln_1p()
+    }
+
+    /// Principal inverse hyperbolic cosine, cuts along the real axis below 1
+    pub fn acosh(&self) -> Self {
+        (*self + (*self + T::one()).sqrt() * (*self - T::one()).sqrt()).ln()
+    }
+
+    /// Principal inverse hyperbolic tangent, cuts along the real axis outside [-1, 1]
+    pub fn atanh(&self) -> Self {
+        let half = T::from(0.5).unwrap();
+        (self.ln_1p() - (-*self).ln_1p()) * half
+    }
+
+    pub fn step(&self) -> Self {
+        Self {
+            real: if self.real > T::zero() { T::one() } else { T::zero() },
+            imag: if self.imag > T::zero() { T::one() } else { T::zero() },
+        }
+    }
+
+    pub fn delta(&self) -> Self {
+        if self.is_zero() {
+            Self::from(T::one())
+        } else {
+            Self::zero()
+        }
+    }
+
+    pub fn exp(&self) -> Self {
+        // On the real axis an infinite modulus would make the imaginary part inf * 0
+        if self.imag.is_zero() {
+            return Self::new(self.real.exp(), self.imag);
+        }
+        Self::from_polar_radians(self.real.exp(), self.imag)
+    }
+
+    /// `exp(self) - 1`, accurate for small arguments
+    pub fn exp_m1(&self) -> Self {
+        if self.imag.is_zero() {
+            return Self::new(self.real.exp_m1(), self.imag);
+        }
+        let two = T::one() + T::one();
+        let half_sin = (self.imag / two).sin();
+        Self {
+            real: self.real.exp_m1() * self.imag.cos() - two * half_sin * half_sin,
+            imag: self.real.exp() * self.imag.sin(),
+        }
+    }
+
+    /// Principal logarithm, imaginary part in (-pi, pi], cut along the negative real axis
+    pub fn ln(&self) -> Self {
+        let modulus = self.modulus();
+        let two = T::one() + T::one();
+        // Near the unit circle ln|z| cancels, so take ln(1 + (|z|^2 - 1)) / 2 instead
+        let real = if modulus > two.recip() && modulus < two {
+            ((self.real - T::one()) * (self.real + T::one()) + self.imag * self.imag).ln_1p() / two
+        } else {
+            modulus.ln()
+        };
+        Self {
+            real,
+            imag: self.angle_radians()
+        }
+    }
+
+    /// `ln(1 + self)`, accurate for small arguments
+    pub fn ln_1p(&self) -> Self {
+        let two = T::one() + T::one();
+        let (x, y) = (self.real, self.imag);
+        // Away from zero, forming 1 + z loses nothing that matters
+        if self.modulus() > T::from(0.5).unwrap() {
+            return (*self + T::one()).ln();
+        }
+        // ln|1 + z| = ln(1 + 2x + x^2 + y^2) / 2
+        Self {
+            real: (two * x + x * x + y * y).ln_1p() / two,
+            imag: y.atan2(T::one() + x),
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.real == T::zero() && self.imag == T::zero()
+    }
+}
+impl<T: Float> Add for Complex<T> {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self {
+            real: self.real + rhs.real,
+            imag: self.imag + rhs.imag,
+        }
+    }
+}
+impl<T: Float> Sum for Complex<T> {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::zero(), |acc: Self, x| acc + x)
+    }
+}
+impl<'a, T: Float> Sum<&'a Complex<T>> for Complex<T> {
+    fn sum<I: Iterator<Item = &'a Complex<T>>>(iter: I) -> Self {
+        iter.fold(Self::zero(), |acc: Self, x| acc + x)
+    }
+}
+impl<T: Float> Product for Complex<T> {
+    fn product<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::from(T::one()), |acc: Self, x| acc * x)
+    }
+}
+impl<'a, T: Float> Product<&'a Complex<T>> for Complex<T> {
+    fn product<I: Iterator<Item = &'a Complex<T>>>(iter: I) -> Self {
+        iter.fold(Self::from(T::one()), |acc: Self, x| acc * x)
+    }
+}
+impl<T: Float> Sub for Complex<T> {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self {
+            real: self.real - rhs.real,
+            imag: self.imag - rhs.imag,
+        }
+    }
+}
+impl<T: Float> Mul<Complex<T>> for Complex<T> {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        Self {
+            real: self.real * rhs.real - self.imag * rhs.imag,
+            imag: self.real * rhs.imag + self.imag * rhs.real,
+        }
+    }
+}
+impl<T: Float> Mul<T> for Complex<T> {
+    type Output = Self;
+
+    fn mul(self, rhs: T) -> Self::Output {
+        Self {
+            real: self.real * rhs,
+            imag: self.imag * rhs,
+        }
+    }
+}
+impl<T: Float> Div<Complex<T>> for Complex<T> {
+    type Output = Self;
+
+    /// Smith's algorithm, which divides through by the larger part of `rhs`
+    /// so neither |rhs|² nor the numerator overflow or underflow on their own
+    fn div(self, rhs: Self) -> Self::Output {
+        if rhs.real.abs() >= rhs.imag.abs() {
+            let ratio = rhs.imag / rhs.real;
+            let denominator = rhs.real + rhs.imag * ratio;
+            Self::new((self.real + self.imag * ratio) / denominator, (self.imag - self.real * ratio) / denominator)
+        } else {
+            let ratio = rhs.real / rhs.imag;
+            let denominator = rhs.real * ratio + rhs.imag;
+            Self::new((self.real * ratio + self.imag) / denominator, (self.imag * ratio - self.real) / denominator)
+        }
+    }
+}
+impl<T: Float> Div<T> for Complex<T> {
+    type Output = Self;
+
+    fn div(self, rhs: T) -> Self::Output {
+        Self {
+            real: self.real / rhs,
+            imag: self.imag / rhs
+        }
+    }
+}
+impl<T: Float> Add<T> for Complex<T> {
+    type Output = Self;
+
+    fn add(self, rhs: T) -> Self::Output {
+        Self {
+            real: self.real + rhs,
+            imag: self.imag
+        }
+    }
+}
+impl<T: Float> Sub<T> for Complex<T> {
+    type Output = Self;
+
+    fn sub(self, rhs: T) -> Self::Output {
+        Self {
+            real: self.real - rhs,
+            imag: self.imag
+        }
+    }
+}
+impl<T: Float> From<T> for Complex<T> {
+    fn from(f: T) -> Self {
+        Self {
+            real: f,
+            imag: T::zero()
+        }
+    }
+}
+impl From<Complex<f32>> for Complex<f64> {
+    fn from(c: Complex<f32>) -> Self {
+        Self {
+            real: c.real.into(),
+            imag: c.imag.into()
+        }
+    }
+}
+impl<T> From<num_complex::Complex<T>> for Complex<T> {
+    fn from(c: num_complex::Complex<T>) -> Self {
+        Self {
+            real: c.re,
+            imag: c.im
+        }
+    }
+}
+impl<T> From<Complex<T>> for num_complex::Complex<T> {
+    fn from(c: Complex<T>) -> Self {
+        Self {
+            re: c.real,
+            im: c.imag
+        }
+    }
+}
+impl<T: Float> Neg for Complex<T> {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        Self {
+            real: -self.real,
+            imag: -self.imag
+        }
+    }
+}
+impl<T: Float> Neg for &Complex<T> {
+    type Output = Complex<T>;
+
+    fn neg(self) -> Self::Output {
+        -*self
+    }
+}
+
+/// Writes `a+bi` or `a-bi`, passing the precision on to both parts
+impl<T: Float + fmt::Display> fmt::Display for Complex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.imag.is_sign_negative() { '-' } else { '+' };
+        match f.precision() {
+            Some(precision) => write!(
+                f,
+                "{:.*}{}{:.*}i",
+                precision,
+                self.real,
+                sign,
+                precision,
+                self.imag.abs()
+            ),
+            None => write!(f, "{}{}{}i", self.real, sign, self.imag.abs()),
+        }
+    }
+}
+
+/// Operators with borrowed operands, for both complex and scalar right hand sides
+macro_rules! forward_ref_binop {
+    ($($imp:ident $method:ident),*) => {$(
+        impl<'a, T: Float> $imp<&'a Complex<T>> for Complex<T> {
+            type Output = Complex<T>;
+
+            fn $method(self, rhs: &'a Complex<T>) -> Self::Output {
+                $imp::$method(self, *rhs)
+            }
+        }
+        impl<'a, T: Float> $imp<Complex<T>> for &'a Complex<T> {
+            type Output = Complex<T>;
+
+            fn $method(self, rhs: Complex<T>) -> Self::Output {
+                $imp::$method(*self, rhs)
+            }
+        }
+        impl<'a, 'b, T: Float> $imp<&'b Complex<T>> for &'a Complex<T> {
+            type Output = Complex<T>;
+
+            fn $method(self, rhs: &'b Complex<T>) -> Self::Output {
+                $imp::$method(*self, *rhs)
+            }
+        }
+        impl<'a, T: Float> $imp<T> for &'a Complex<T> {
+            type Output = Complex<T>;
+
+            fn $method(self, rhs: T) -> Self::Output {
+                $imp::$method(*self, rhs)
+            }
+        }
+    )*};
+}
+forward_ref_binop!(Add add, Sub sub, Mul mul, Div div);
+
+/// Compound assignment in terms of the binary operators
+macro_rules! assign_op {
+    ($($imp:ident $method:ident $op:ident $op_method:ident),*) => {$(
+        impl<T: Float> $imp for Complex<T> {
+            fn $method(&mut self, rhs: Complex<T>) {
+                *self = $op::$op_method(*self, rhs);
+            }
+        }
+        impl<'a, T: Float> $imp<&'a Complex<T>> for Complex<T> {
+            fn $method(&mut self, rhs: &'a Complex<T>) {
+                *self = $op::$op_method(*self, *rhs);
+            }
+        }
+        impl<T: Float> $imp<T> for Complex<T> {
+            fn $method(&mut self, rhs: T) {
+                *self = $op::$op_method(*self, rhs);
+            }
+        }
+    )*};
+}
+assign_op!(
+    AddAssign add_assign Add add,
+    SubAssign sub_assign Sub sub,
+    MulAssign mul_assign Mul mul,
+    DivAssign div_assign Div div
+);
+
+/// Scalars on the left, which the orphan rule only allows per concrete float
+macro_rules! scalar_lhs_binop {
+    ($($scalar:ty),*) => {$(
+        impl Add<Complex<$scalar>> for $scalar {
+            type Output = Complex<$scalar>;
+
+            fn add(self, rhs: Complex<$scalar>) -> Self::Output {
+                rhs + self
+            }
+        }
+        impl Sub<Complex<$scalar>> for $scalar {
+            type Output = Complex<$scalar>;
+
+            fn sub(self, rhs: Complex<$scalar>) -> Self::Output {
+                Complex::from(self) - rhs
+            }
+        }
+        impl Mul<Complex<$scalar>> for $scalar {
+            type Output = Complex<$scalar>;
+
+            fn mul(self, rhs: Complex<$scalar>) -> Self::Output {
+                rhs * self
+            }
+        }
+        impl Div<Complex<$scalar>> for $scalar {
+            type Output = Complex<$scalar>;
+
+            fn div(self, rhs: Complex<$scalar>) -> Self::Output {
+                Complex::from(self) / rhs
+            }
+        }
+        impl<'a> Mul<&'a Complex<$scalar>> for $scalar {
+            type Output = Complex<$scalar>;
+
+            fn mul(self, rhs: &'a Complex<$scalar>) -> Self::Output {
+                *rhs * self
+            }
+        }
+    )*};
+}
+scalar_lhs_binop!(f32, f64);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn complex() -> impl Strategy<Value = Complex> {
+        (-1e3..1e3f64, -1e3..1e3f64).prop_map(|(real, imag)| Complex::new(real, imag))
+    }
+
+    fn nonzero() -> impl Strategy<Value = Complex> {
+        complex().prop_filter("nonzero", |z| z.modulus() > 1e-3)
+    }
+
+    /// Equal up to rounding, relative to the size of the terms that went in
+    fn assert_close(a: Complex, b: Complex, scale: f64) {
+        let tolerance = 1e-12 * scale.max(1.0);
+        assert!((a - b).modulus() <= tolerance, "{:?} != {:?}", a, b);
+    }
+
+    /// Componentwise agreement, loose only where a part is tiny next to the whole
+    fn assert_matches(got: Complex, expected: Complex, context: &str) {
+        let scale = expected.modulus();
+        let agrees = |got: f64, expected: f64| {
+            if expected.is_nan() {
+                got.is_nan()
+            } else if expected.is_infinite() {
+                got == expected
+            } else {
+                (got - expected).abs() <= 4e-15 * expected.abs() + 1e-15 * scale
+            }
+        };
+        assert!(
+            agrees(got.real, expected.real) && agrees(got.imag, expected.imag),
+            "{}: got {:?}, expected {:?}",
+            context,
+            got,
+            expected
+        );
+    }
+
+    /// Principal values from mpmath at 50 digits. Points on a branch cut appear
+    /// with both signs of zero, each matching the limit from that side.
+    #[rustfmt::skip]
+    #[allow(clippy::approx_constant)]
+    const REFERENCE: &[(&str, f64, f64, f64, f64)] = &[
+        ("exp", 0.0, 0.0, 1.0, 0.0),
+        ("exp", 1.0, 0.0, 2.718281828459045, 0.0),
+        ("exp", 0.0, 3.141592653589793, -1.0, 1.2246467991473532e-16),
+        ("exp", -2.0, 3.0, -0.13398091492954262, 0.019098516261135196),
+        ("exp", 10.0, -0.5, 19330.042281597143, -10560.050227222264),
+        ("exp", 1e-10, 1e-10, 1.0000000001, 1.0000000001000001e-10),
+        ("exp", -700.0, 1.0, 5.327205971707415e-305, 8.296631731164852e-305),
+        ("exp", 800.0, 0.0, f64::INFINITY, 0.0),
+        ("exp", 800.0, -0.0, f64::INFINITY, -0.0),
+        ("ln", 1.0, 0.0, 0.0, 0.0),
+        ("ln", -1.0, 0.0, 0.0, 3.141592653589793),
+        ("ln", -1.0, -0.0, 0.0, -3.141592653589793),
+        ("ln", -2.5, 0.0, 0.9162907318741551, 3.141592653589793),
+        ("ln", -2.5, -0.0, 0.9162907318741551, -3.141592653589793),
+        ("ln", 2.0, 3.0, 1.2824746787307684, 0.982793723247329),
+        ("ln", 1e+300, 1e+300, 691.1221014884936, 0.7853981633974483),
+        ("ln", 1e-300, -1e-300, -690.4289543079337, -0.7853981633974483),
+        ("ln", 1.0, 1e-10, 5.0000000000000005e-21, 1e-10),
+        ("ln", 0.0, -4.0, 1.3862943611198906, -1.5707963267948966),
+        ("sqrt", -4.0, 0.0, 0.0, 2.0),
+        ("sqrt", -4.0, -0.0, 0.0, -2.0),
+        ("sqrt", 3.0, 4.0, 2.0, 1.0),
+        ("sqrt", 0.0, 2.0, 1.0, 1.0),
+        ("sqrt", -1e+300, 1e+300, 4.550898605622274e+149, 1.09868411346781e+150),
+        ("sqrt", 1e-300, 1e-300, 1.09868411346781e-150, 4.5508986056222734e-151),
+        ("sqrt", -3.0, -4.0, 1.0, -2.0),
+        ("sqrt", 0.25, 0.0, 0.5, 0.0),
+        ("sqrt", f64::MAX, 0.0, 1.3407807929942596e+154, 0.0),
+        ("sqrt", -f64::MAX, 0.0, 0.0, 1.3407807929942596e+154),
+        ("sqrt", f64::MAX, f64::MAX, 1.4730945569055652e+154, 6.1017574412827024e+153),
+        ("sqrt", -f64::MAX, -f64::MAX, 6.1017574412827024e+153, -1.4730945569055652e+154),
+        ("sqrt", f64::MAX, 1.0, 1.3407807929942596e+154, 3.7291703656001034e-155),
+        ("sqrt", f64::INFINITY, 1.0, f64::INFINITY, 0.0),
+        ("sqrt", -f64::INFINITY, 1.0, 0.0, f64::INFINITY),
+        ("asin", 2.0, 0.0, 1.5707963267948966, 1.3169578969248168),
+        ("asin", 2.0, -0.0, 1.5707963267948966, -1.3169578969248168),
+        ("asin", -2.0, 0.0, -1.5707963267948966, 1.3169578969248168),
+        ("asin", -2.0, -0.0, -1.5707963267948966, -1.3169578969248168),
+        ("asin", 0.5, 0.5, 0.4522784471511907, 0.5306375309525179),
+        ("asin", -3.0, 4.0, -0.6339838656391767, 2.305509031243477),
+        ("asin", 1e-08, -2e-08, 9.999999999999999e-09, -2e-08),
+        ("asin", 2.0, -3.0, 0.5706527843210994, -1.9833870299165355),
+        ("asin", -0.25, -7.5, -0.03302922861756942, -2.7130060861946985),
+        ("asin", 1e-09, 1e-09, 1e-09, 1e-09),
+        ("asin", 10000000000.0, 10000000000.0, 0.7853981633974483, 24.065571700780374),
+        ("acos", 2.0, 0.0, 0.0, -1.3169578969248168),
+        ("acos", 2.0, -0.0, 0.0, 1.3169578969248168),
+        ("acos", -2.0, 0.0, 3.141592653589793, -1.3169578969248168),
+        ("acos", -2.0, -0.0, 3.141592653589793, 1.3169578969248168),
+        ("acos", 0.5, 0.5, 1.1185178796437059, -0.5306375309525179),
+        ("acos", -3.0, 4.0, 2.2047801924340735, -2.305509031243477),
+        ("acos", 1e-08, -2e-08, 1.5707963167948966, 2e-08),
+        ("acos", 2.0, -3.0, 1.0001435424737972, 1.9833870299165355),
+        ("acos", -0.25, -7.5, 1.603825555412466, 2.7130060861946985),
+        ("acos", 1e-09, 1e-09, 1.5707963257948967, -1e-09),
+        ("acos", 10000000000.0, -10000000000.0, 0.7853981633974483, 24.065571700780374),
+        ("atan", 0.0, 2.0, 1.5707963267948966, 0.5493061443340549),
+        ("atan", -0.0, 2.0, -1.5707963267948966, 0.5493061443340549),
+        ("atan", 0.0, -2.0, 1.5707963267948966, -0.5493061443340549),
+        ("atan", -0.0, -2.0, -1.5707963267948966, -0.5493061443340549),
+        ("atan", 0.5, 0.5, 0.5535743588970452, 0.40235947810852507),
+        ("atan", -3.0, 4.0, -1.4483069952314644, 0.15899719167999918),
+        ("atan", 1e-08, -2e-08, 1.0000000000000004e-08, -2e-08),
+        ("atan", 2.0, -3.0, 1.4099210495965755, -0.22907268296853878),
+        ("atan", -0.25, -7.5, -1.5662766758863051, -0.1339786135703504),
+        ("atan", 1e-09, 1e-09, 1e-09, 1e-09),
+        ("atan", 10000000000.0, 10000000000.0, 1.5707963267448966, 5e-11),
+        ("asinh", 0.0, 2.0, 1.3169578969248168, 1.5707963267948966),
+        ("asinh", -0.0, 2.0, -1.3169578969248168, 1.5707963267948966),
+        ("asinh", 0.0, -2.0, 1.3169578969248168, -1.5707963267948966),
+        ("asinh", -0.0, -2.0, -1.3169578969248168, -1.5707963267948966),
+        ("asinh", 0.5, 0.5, 0.5306375309525179, 0.4522784471511907),
+        ("asinh", -3.0, 4.0, -2.2999140408792695, 0.9176168533514787),
+        ("asinh", 1e-08, -2e-08, 1.0000000000000002e-08, -2e-08),
+        ("asinh", 2.0, -3.0, 1.9686379257930964, -0.9646585044076028),
+        ("asinh", -0.25, -7.5, -2.704146200357981, -1.5371757043897178),
+        ("asinh", 1e-09, 1e-09, 1e-09, 1e-09),
+        ("asinh", 10000000000.0, 10000000000.0, 24.065571700780374, 0.7853981633974483),
+        ("acosh", 0.5, 0.0, 0.0, 1.0471975511965979),
+        ("acosh", 0.5, -0.0, 0.0, -1.0471975511965979),
+        ("acosh", -2.0, 0.0, 1.3169578969248168, 3.141592653589793),
+        ("acosh", -2.0, -0.0, 1.3169578969248168, -3.141592653589793),
+        ("acosh", 0.5, 0.5, 0.5306375309525179, 1.1185178796437059),
+        ("acosh", -3.0, 4.0, 2.305509031243477, 2.2047801924340735),
+        ("acosh", 1e-08, -2e-08, 2e-08, -1.5707963167948966),
+        ("acosh", 2.0, -3.0, 1.9833870299165355, -1.0001435424737972),
+        ("acosh", -0.25, -7.5, 2.7130060861946985, -1.603825555412466),
+        ("acosh", 10000000000.0, 10000000000.0, 24.065571700780374, 0.7853981633974483),
+        ("atanh", 2.0, 0.0, 0.5493061443340549, 1.5707963267948966),
+        ("atanh", 2.0, -0.0, 0.5493061443340549, -1.5707963267948966),
+        ("atanh", -2.0, 0.0, -0.5493061443340549, 1.5707963267948966),
+        ("atanh", -2.0, -0.0, -0.5493061443340549, -1.5707963267948966),
+        ("atanh", 0.5, 0.5, 0.40235947810852507, 0.5535743588970452),
+        ("atanh", -3.0, 4.0, -0.1175009073114339, 1.4099210495965755),
+        ("atanh", 1e-08, -2e-08, 9.999999999999997e-09, -2e-08),
+        ("atanh", 2.0, -3.0, 0.14694666622552977, -1.3389725222944935),
+        ("atanh", -0.25, -7.5, -0.00436216083373659, -1.4383876618420022),
+        ("atanh", 1e-09, 1e-09, 1e-09, 1e-09),
+        ("atanh", 10000000000.0, 10000000000.0, 5e-11, 1.5707963267448966),
+        ("exp_m1", 1e-10, 0.0, 1.00000000005e-10, 0.0),
+        ("exp_m1", 1e-10, 1e-10, 1e-10, 1.0000000001000001e-10),
+        ("exp_m1", -3e-12, 5e-09, -3.0000124999955003e-12, 4.999999999985e-09),
+        ("exp_m1", 1e-05, -2e-05, 9.999849998166664e-06, -2.0000199999666657e-05),
+        ("exp_m1", 0.5, 0.5, 0.4468890365841692, 0.7904390832136149),
+        ("exp_m1", 0.0, 1e-08, -5e-17, 1e-08),
+        ("exp_m1", 800.0, 0.0, f64::INFINITY, 0.0),
+        ("ln_1p", 1e-10, 0.0, 9.999999999500001e-11, 0.0),
+        ("ln_1p", 1e-10, 1e-10, 1e-10, 9.999999999e-11),
+        ("ln_1p", -3e-12, 5e-09, -2.9999875000045e-12, 5.000000000015e-09),
+        ("ln_1p", 1e-05, -2e-05, 1.0000149996333351e-05, -1.9999799999333396e-05),
+        ("ln_1p", 0.3, -0.4, 0.30759281954511675, -0.2984989315861793),
+        ("ln_1p", -0.5, 0.25, -0.5815754049028404, 0.4636476090008061),
+        ("ln_1p", 2.0, 3.0, 1.4451858789480823, 0.7853981633974483),
+    ];
+
+    #[test]
+    fn elementary_functions_match_reference_values() {
+        for &(name, real, imag, expected_real, expected_imag) in REFERENCE {
+            let z = Complex::new(real, imag);
+            let got = match name {
+                "exp" => z.exp(),
+                "exp_m1" => z.exp_m1(),
+                "ln" => z.ln(),
+                "ln_1p" => z.ln_1p(),
+                "sqrt" => z.sqrt(),
+                "asin" => z.asin(),
+                "acos" => z.acos(),
+                "atan" => z.atan(),
+                "asinh" => z.asinh(),
+                "acosh" => z.acosh(),
+                "atanh" => z.atanh(),
+                _ => unreachable!(),
+            };
+            let context = format!("{}({:?})", name, z);
+            assert_matches(got, Complex::new(expected_real, expected_imag), &context);
+        }
+    }
+
+    /// Principal powers from mpmath, plus the zero base, which has a pole for
+    /// negative real exponents and no limit once the exponent has an imaginary part
+    type Pair = (f64, f64);
+
+    #[rustfmt::skip]
+    #[allow(clippy::approx_constant)]
+    const POWERS: &[(Pair, Pair, Pair)] = &[
+        ((2.0, 0.0), (0.5, 0.0), (1.4142135623730951, 0.0)),
+        ((1.0, 1.0), (2.0, -1.0), (1.4900141243594482, 4.12574447016181)),
+        ((-8.0, 0.0), (0.3333333333333333, 0.0), (1.0, 1.7320508075688772)),
+        ((0.0, 1.0), (0.0, 1.0), (0.2078795763507619, 0.0)),
+        ((3.0, -4.0), (-2.5, 0.0), (-0.01216, 0.01312)),
+        ((1e-05, 2.0), (0.3, 0.7), (0.23633449695804482, 0.33502550213542387)),
+        ((1.5, 0.0), (100.0, 0.0), (4.065611775352152e+17, 0.0)),
+        ((-1.0, -0.0), (0.5, 0.0), (0.0, -1.0)),
+        ((0.0, 0.0), (2.0, 0.0), (0.0, 0.0)),
+        ((0.0, 0.0), (0.0, 0.0), (1.0, 0.0)),
+        ((0.0, 0.0), (-1.0, 0.0), (f64::INFINITY, 0.0)),
+        ((0.0, 0.0), (-1.0, 1.0), (f64::NAN, f64::NAN)),
+    ];
+
+    #[test]
+    fn powf_matches_reference_values() {
+        for &(base, exponent, expected) in POWERS {
+            let (base, exponent) = (Complex::new(base.0, base.1), Complex::new(exponent.0, exponent.1));
+            let context = format!("({:?}).powf({:?})", base, exponent);
+            assert_matches(base.powf(&exponent), Complex::new(expected.0, expected.1), &context);
+        }
+    }
+
+    #[test]
+    fn division_scales_by_the_larger_part() {
+        let huge = Complex::new(1e200, 1e200);
+        assert_eq!(huge / huge, Complex::from(1.0));
+        assert_matches(huge.inverse(), Complex::new(5e-201, -5e-201), "1 / (1e200 + 1e200i)");
+        let tiny = Complex::new(1e-200, -3e-200);
+        assert_matches(tiny / tiny, Complex::from(1.0), "tiny / tiny");
+        assert_matches(Complex::from(1e-300) / Complex::new(0.0, 1e10), Complex::new(0.0, -1e-310), "1e-300 / 1e10i");
+    }
+
+    #[test]
+    fn num_complex_round_trip() {
+        for z in [Complex::new(1.5, -2.0), Complex::new(-0.0, 0.0), Complex::new(f64::INFINITY, f64::MIN_POSITIVE)] {
+            let converted: num_complex::Complex<f64> = z.into();
+            assert_eq!((converted.re.to_bits(), converted.im.to_bits()), (z.real.to_bits(), z.imag.to_bits()));
+            assert_eq!(Complex::from(converted), z);
+        }
+        let nan = Complex::from(num_complex::Complex::new(f64::NAN, 1.0));
+        assert!(nan.real.is_nan() && nan.imag == 1.0);
+        assert_eq!(Complex::from(num_complex::Complex::new(0.5f32, 2.0)), Complex::new(0.5f32, 2.0));
+    }
+
+    proptest! {
+        #[test]
+        fn ring_identities(a in complex(), b in complex(), c in complex()) {
+            let scale = (a.modulus() + 1.0) * (b.modulus() + 1.0) * (c.modulus() + 1.0);
+            prop_assert_eq!(a + b, b + a);
+            prop_assert_eq!(a * b, b * a);
+            assert_close((a + b) + c, a + (b + c), scale);
+            assert_close((a * b) * c, a * (b * c), scale);
+            assert_close(a * (b + c), a * b + a * c, scale);
+            prop_assert_eq!(a + Complex::zero(), a);
+            prop_assert_eq!(a * Complex::from(1.0), a);
+            prop_assert_eq!(a - a, Complex::zero());
+            prop_assert_eq!(a + -a, Complex::zero());
+            prop_assert_eq!(-(-a), a);
+        }
+
+        #[test]
+        fn division_inverts_multiplication(a in complex(), b in nonzero()) {
+            assert_close((a / b) * b, a, a.modulus());
+            assert_close(a * b.inverse(), a / b, a.modulus() / b.modulus());
+        }
+
+        #[test]
+        fn division_survives_extreme_magnitudes(
+            a in nonzero(),
+            b in nonzero(),
+            a_scale in -140..140i32,
+            b_scale in -140..140i32,
+        ) {
+            let (a, b) = (a * 10f64.powi(a_scale), b * 10f64.powi(b_scale));
+            let quotient = a / b;
+            prop_assert!((quotient * b - a).modulus() <= 1e-14 * a.modulus(), "{:?} / {:?} = {:?}", a, b, quotient);
+            prop_assert!((b / b - Complex::from(1.0)).modulus() <= 1e-15);
+            prop_assert!((b.inverse() * b - Complex::from(1.0)).modulus() <= 1e-15);
+        }
+
+        #[test]
+        fn assign_ops_match_binary_ops(a in complex(), b in nonzero(), s in 1e-3..1e3f64) {
+            let assigned = |op: fn(&mut Complex, Complex)| { let mut z = a; op(&mut z, b); z };
+            prop_assert_eq!(assigned(|z, b| *z += b), a + b);
+            prop_assert_eq!(assigned(|z, b| *z -= b), a - b);
+            prop_assert_eq!(assigned(|z, b| *z *= b), a * b);
+            prop_assert_eq!(assigned(|z, b| *z /= b), a / b);
+
+            let assigned = |op: fn(&mut Complex, &Complex)| { let mut z = a; op(&mut z, &b); z };
+            prop_assert_eq!(assigned(|z, b| *z += b), a + b);
+            prop_assert_eq!(assigned(|z, b| *z -= b), a - b);
+            prop_assert_eq!(assigned(|z, b| *z *= b), a * b);
+            prop_assert_eq!(assigned(|z, b| *z /= b), a / b);
+
+            let assigned = |op: fn(&mut Complex, f64)| { let mut z = a; op(&mut z, s); z };
+            prop_assert_eq!(assigned(|z, s| *z += s), a + s);
+            prop_assert_eq!(assigned(|z, s| *z -= s), a - s);
+            prop_assert_eq!(assigned(|z, s| *z *= s), a * s);
+            prop_assert_eq!(assigned(|z, s| *z /= s), a / s);
+        }
+
+        #[test]
+        #[allow(clippy::op_ref)]
+        fn reference_ops_match_owned_ops(a in complex(), b in nonzero(), s in 1e-3..1e3f64) {
+            prop_assert_eq!(&a + &b, a + b);
+            prop_assert_eq!(&a - b, a - b);
+            prop_assert_eq!(a * &b, a * b);
+            prop_assert_eq!(&a / &b, a / b);
+            prop_assert_eq!(&a + s, a + s);
+            prop_assert_eq!(&a - s, a - s);
+            prop_assert_eq!(&a * s, a * s);
+            prop_assert_eq!(&a / s, a / s);
+            prop_assert_eq!(s * &a, s * a);
+            prop_assert_eq!(-&a, -a);
+            prop_assert_eq!([a, b].iter().sum::<Complex>(), a + b);
+            prop_assert_eq!([a, b].iter().product::<Complex>(), a * b);
+        }
+
+        #[test]
+        fn scalar_ops_match_complex_ops(a in nonzero(), s in prop_oneof![-1e3..-1e-3f64, 1e-3..1e3f64]) {
+            let scalar = Complex::from(s);
+            let scale = a.modulus() * s.abs() + a.modulus() / s.abs() + s.abs() / a.modulus();
+            assert_close(a * s, a * scalar, scale);
+            assert_close(s * a, scalar * a, scale);
+            assert_close(a / s, a / scalar, scale);
+            assert_close(s / a, scalar / a, scale);
+            prop_assert_eq!(a + s, a + scalar);
+            prop_assert_eq!(s + a, scalar + a);
+            prop_assert_eq!(a - s, a - scalar);
+            prop_assert_eq!(s - a, scalar - a);
+        }
+
+        #[test]
+        fn single_precision_scalars(real in -1e3..1e3f32, imag in -1e3..1e3f32, s in 1e-2..1e2f32) {
+            let a = Complex::new(real, imag);
+            prop_assert_eq!(a * s, Complex::new(real * s, imag * s));
+            prop_assert_eq!(s * a, a * s);
+            prop_assert_eq!(a / s, Complex::new(real / s, imag / s));
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use crate::experiment::Grid;
+use serde::{Deserialize, Serialize};
+
+// IPC payloads stay in f32, computations run in f64
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct Datapoint {
+    pub x: f32,
+    pub y: f32,
+}
+impl Datapoint {
+    pub fn new(x: f64, y: f64) -> Self {
+        Self {
+            x: x as f32,
+            y: y as f32,
+        }
+    }
+}
+
+/// A curve as the frontend draws it
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+pub struct Datapoints {
+    pub values: Vec<Datapoint>,
+}
+impl Datapoints {
+    pub fn new(values: Vec<Datapoint>) -> Self {
+        Self { values }
+    }
+
+    /// Pairs `values` with the positions of the grid
+    pub fn on_grid(grid: &Grid, values: impl IntoIterator<Item = f64>) -> Self {
+        Self::new(grid.positions().zip(values).map(|(x, y)| Datapoint::new(x, y)).collect())
+    }
+}
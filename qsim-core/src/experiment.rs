@@ -0,0 +1,364 @@
+use crate::complex::Complex;
+use crate::formula::{Formula, FormulaError, Operator};
+use crate::import::{self, Interpolation};
+use crate::linear::{EigenBasis, TridiagonalMatrix, Vector};
+use serde::{Deserialize, Serialize};
+
+/// `resolution` evenly spaced points starting at `start`, the last one a step short of `end`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "GridFields")]
+pub struct Grid {
+    start: f64,
+    end: f64,
+    resolution: u32,
+}
+/// Unchecked `Grid`, so deserializing goes through `Grid::new`
+#[derive(Deserialize)]
+struct GridFields {
+    start: f64,
+    end: f64,
+    resolution: u32,
+}
+impl TryFrom<GridFields> for Grid {
+    type Error = String;
+
+    fn try_from(fields: GridFields) -> Result<Self, Self::Error> {
+        Grid::new(fields.start, fields.end, fields.resolution)
+    }
+}
+impl Grid {
+    pub fn new(start: f64, end: f64, resolution: u32) -> Result<Self, String> {
+        if !start.is_finite() || !end.is_finite() || start >= end {
+            return Err("Grid bounds must be finite and increasing".into());
+        }
+        if resolution == 0 {
+            return Err("Grid needs at least one point".into());
+        }
+        Ok(Self { start, end, resolution })
+    }
+
+    pub fn start(&self) -> f64 {
+        self.start
+    }
+
+    pub fn end(&self) -> f64 {
+        self.end
+    }
+
+    pub fn resolution(&self) -> u32 {
+        self.resolution
+    }
+
+    pub fn spacing(&self) -> f64 {
+        (self.end - self.start) / self.resolution as f64
+    }
+
+    pub fn position(&self, i: usize) -> f64 {
+        self.start + (i as f64) * self.spacing()
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = f64> + '_ {
+        (0..self.resolution as usize).map(move |i| self.position(i))
+    }
+}
+
+/// Potential energy, either as a formula in `x`, already sampled on the grid, or
+/// as `(x, V)` points to interpolate onto it
+#[derive(Debug, Clone)]
+pub enum Potential {
+    Formula(Formula),
+    Samples(Vec<f64>),
+    Interpolated(Vec<(f64, f64)>, Interpolation),
+}
+impl Potential {
+    pub fn parse(formula: &str) -> Result<Self, FormulaError> {
+        Formula::new(formula).map(Potential::Formula)
+    }
+
+    pub fn sample(&self, grid: &Grid) -> Result<Vector, String> {
+        match self {
+            Potential::Formula(formula) => Ok(Vector::from(formula.get_vector(
+                grid.start,
+                grid.end,
+                grid.resolution,
+            ))),
+            Potential::Samples(values) => {
+                if values.len() != grid.resolution as usize {
+                    return Err("Potential samples don't match the grid".into());
+                }
+                Ok(Vector::from(values.iter().map(|value| Complex::from(*value)).collect::<Vec<Complex>>()))
+            }
+            Potential::Interpolated(points, interpolation) => {
+                let values = import::interpolate(points, grid, *interpolation)
+                    .map_err(|error| format!("potential: {}", error))?;
+                Ok(Vector::from(values.into_iter().map(Complex::from).collect::<Vec<Complex>>()))
+            }
+        }
+    }
+}
+
+/// Wavefunction at time zero, before the momentum boost. Samples are `(x, ψ(x))`
+/// pairs, one per grid point, while interpolated points may be anywhere on it
+#[derive(Debug, Clone)]
+pub enum InitialState {
+    Formula(Formula),
+    Samples(Vec<(f64, f64)>),
+    Interpolated(Vec<(f64, f64)>, Interpolation),
+}
+impl InitialState {
+    pub fn parse(formula: &str) -> Result<Self, FormulaError> {
+        Formula::new(formula).map(InitialState::Formula)
+    }
+
+    /// The normalized state on the grid, multiplied by `e^(i momentum x)`
+    pub fn sample(&self, grid: &Grid, momentum: f64) -> Result<Vector, String> {
+        let phase = Formula::complex_phase(momentum);
+        let mut wavefunction = match self {
+            InitialState::Formula(formula) => {
+                let formula = formula.clone().adjoin(phase, Operator::Multiplication);
+                Vector::from(formula.get_vector(grid.start, grid.end, grid.resolution))
+            }
+            InitialState::Samples(samples) => {
+                if samples.len() != grid.resolution as usize {
+                    return Err("Wavefunction samples don't match the grid".into());
+                }
+                Vector::from(
+                    samples
+                        .iter()
+                        .map(|(x, y)| Ok(*y * phase.evaluate_complex(Complex::from(*x))?))
+                        .collect::<Result<Vec<Complex>, String>>()?,
+                )
+            }
+            InitialState::Interpolated(points, interpolation) => {
+                let values = import::interpolate(points, grid, *interpolation)
+                    .map_err(|error| format!("wavefunction: {}", error))?;
+                Vector::from(
+                    grid.positions()
+                        .zip(values)
+                        .map(|(x, y)| Ok(y * phase.evaluate_complex(Complex::from(x))?))
+                        .collect::<Result<Vec<Complex>, String>>()?,
+                )
+            }
+        };
+        wavefunction.normalize();
+        // A large momentum overflows the phase
+        let finite = |value: &Complex| value.real.is_finite() && value.imag.is_finite();
+        if let Some((x, _)) = grid.positions().zip(wavefunction.iter()).find(|(_, value)| !finite(value)) {
+            return Err(format!("The wavefunction is not finite at x = {}", x));
+        }
+        Ok(wavefunction)
+    }
+}
+
+/// Numerical settings of the eigenbasis pipeline
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "SolverFields")]
+pub struct Solver {
+    /// Bound on the QL sweeps spent on each eigenvalue
+    iterations: u32,
+    /// Fraction of the spectrum, from the ground state up, kept in the superposition
+    kept_fraction: f64,
+}
+/// Unchecked `Solver`, so deserializing goes through `Solver::new`
+#[derive(Deserialize)]
+#[serde(default)]
+struct SolverFields {
+    iterations: u32,
+    kept_fraction: f64,
+}
+impl Default for SolverFields {
+    fn default() -> Self {
+        let Solver { iterations, kept_fraction } = Solver::default();
+        Self { iterations, kept_fraction }
+    }
+}
+impl TryFrom<SolverFields> for Solver {
+    type Error = String;
+
+    fn try_from(fields: SolverFields) -> Result<Self, Self::Error> {
+        Solver::new(fields.iterations, fields.kept_fraction)
+    }
+}
+impl Default for Solver {
+    fn default() -> Self {
+        Self { iterations: 30, kept_fraction: 0.5 }
+    }
+}
+impl Solver {
+    pub fn new(iterations: u32, kept_fraction: f64) -> Result<Self, String> {
+        if iterations == 0 {
+            return Err("Solver needs at least one iteration".into());
+        }
+        if !(kept_fraction > 0.0 && kept_fraction <= 1.0) {
+            return Err("Kept fraction must be above 0 and at most 1".into());
+        }
+        Ok(Self { iterations, kept_fraction })
+    }
+
+    pub fn iterations(&self) -> u32 {
+        self.iterations
+    }
+
+    pub fn kept_fraction(&self) -> f64 {
+        self.kept_fraction
+    }
+
+    /// Diagonalizes the Hamiltonian and expands the initial state in its eigenbasis
+    pub fn solve(
+        &self,
+        grid: Grid,
+        potential: &Potential,
+        initial_state: &InitialState,
+        momentum: f64,
+    ) -> Result<Experiment, String> {
+        let potential = potential.sample(&grid)?;
+        let wavefunction = initial_state.sample(&grid, momentum)?;
+
+        let basis = EigenBasis::new(hamiltonian(&potential).eigenproblem(self.iterations)?)?;
+        let kept = (grid.resolution as f64 * self.kept_fraction) as usize;
+        let coefficients = Vector::from(
+            basis
+                .project(&wavefunction)?
+                .to_vec()
+                .into_iter()
+                .enumerate()
+                .map(|(i, c)| if i > kept { Complex::zero() } else { c })
+                .collect::<Vec<Complex>>(),
+        );
+
+        Ok(Experiment { grid, potential, wavefunction, basis, coefficients })
+    }
+}
+
+/// Discretized H = -d²/dx² + V in units of the grid spacing
+/// http://facweb1.redlands.edu/fac/eric_hill/Phys341/Computation/Comp%201%20Setting%20up%20the%20discrete%20Schr%C3%B6dinger%20equation.pdf
+pub fn hamiltonian(potential: &Vector) -> TridiagonalMatrix {
+    let n = potential.len();
+    let hopping = vec![Complex::from(-1.0); n.saturating_sub(1)];
+    // An imaginary potential would make H non-Hermitian
+    let diagonal = potential.iter().map(|value| Complex::from(2.0 + value.real)).collect();
+
+    TridiagonalMatrix::from_diagonals(hopping.clone(), diagonal, hopping).unwrap()
+}
+
+/// A solved experiment, ready to be evolved to any time
+#[derive(Debug, Clone)]
+pub struct Experiment {
+    grid: Grid,
+    potential: Vector,
+    wavefunction: Vector,
+    basis: EigenBasis,
+    coefficients: Vector,
+}
+impl Experiment {
+    pub fn grid(&self) -> &Grid {
+        &self.grid
+    }
+
+    pub fn potential(&self) -> &Vector {
+        &self.potential
+    }
+
+    /// The normalized initial state
+    pub fn wavefunction(&self) -> &Vector {
+        &self.wavefunction
+    }
+
+    pub fn basis(&self) -> &EigenBasis {
+        &self.basis
+    }
+
+    /// Coefficients of the initial state in the eigenbasis, zero above the kept part of the spectrum
+    pub fn coefficients(&self) -> &Vector {
+        &self.coefficients
+    }
+
+    pub fn eigenvalues(&self) -> &[f64] {
+        self.basis.energies()
+    }
+
+    pub fn eigenvector(&self, n: usize) -> Option<&[Complex]> {
+        self.basis.vector(n)
+    }
+
+    /// The wavefunction at `time`
+    pub fn evolve(&self, time: f64) -> Result<Vector, String> {
+        self.basis.frame(&self.coefficients, time)
+    }
+
+    pub fn evolve_many(&self, times: &[f64]) -> Result<Vec<Vector>, String> {
+        self.basis.frames(&self.coefficients, times)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_rejects_invalid_bounds_and_resolution() {
+        assert!(Grid::new(0.0, 1.0, 0).is_err());
+        assert!(Grid::new(1.0, 1.0, 10).is_err());
+        assert!(Grid::new(2.0, -2.0, 10).is_err());
+        assert!(Grid::new(f64::NAN, 1.0, 10).is_err());
+        assert!(Grid::new(0.0, f64::INFINITY, 10).is_err());
+        assert!(serde_json::from_str::<Grid>(r#"{"start": 1.0, "end": 0.0, "resolution": 4}"#).is_err());
+    }
+
+    #[test]
+    fn solver_rejects_invalid_settings() {
+        assert!(Solver::new(0, 0.5).is_err());
+        assert!(Solver::new(30, 0.0).is_err());
+        assert!(Solver::new(30, 1.5).is_err());
+        assert!(Solver::new(30, f64::NAN).is_err());
+        assert_eq!(Solver::new(1, 1.0).unwrap().kept_fraction(), 1.0);
+        assert_eq!(serde_json::from_str::<Solver>("{}").unwrap(), Solver::default());
+        assert_eq!(serde_json::from_str::<Solver>(r#"{"iterations": 45}"#).unwrap(), Solver::new(45, 0.5).unwrap());
+        assert!(serde_json::from_str::<Solver>(r#"{"iterations": 0}"#).is_err());
+        assert!(serde_json::from_str::<Solver>(r#"{"kept_fraction": 2.0}"#).is_err());
+        assert!(serde_json::from_str::<Solver>(r#"{"kept_fraction": -0.5}"#).is_err());
+    }
+
+    #[test]
+    fn grid_positions_stop_a_step_short_of_the_end() {
+        let grid = Grid::new(-1.0, 1.0, 4).unwrap();
+        assert_eq!(grid.spacing(), 0.5);
+        assert_eq!(grid.positions().collect::<Vec<f64>>(), vec![-1.0, -0.5, 0.0, 0.5]);
+        assert_eq!(serde_json::from_str::<Grid>(r#"{"start": -1.0, "end": 1.0, "resolution": 4}"#).unwrap(), grid);
+    }
+
+    /// With H = -d²/dx² + V in grid units (ħ = 1, m = 1/2), V = x² over
+    /// x = 200 j on [-1, 1) is the oscillator ω²j²/4 with ω = 0.01
+    #[test]
+    fn harmonic_oscillator_levels_are_evenly_spaced() {
+        let grid = Grid::new(-1.0, 1.0, 400).unwrap();
+        let potential = Potential::parse("x^2").unwrap();
+        let initial_state = InitialState::parse("exp(-100x^2)").unwrap();
+        let experiment = Solver::default().solve(grid, &potential, &initial_state, 0.0).unwrap();
+
+        // The three-point Laplacian lowers level n by about ω²(2n² + 2n + 1)/64
+        let omega = 0.01;
+        for (n, energy) in experiment.eigenvalues().iter().take(6).enumerate() {
+            let expected = (n as f64 + 0.5) * omega;
+            assert!((energy - expected).abs() < 1e-2 * expected, "E_{} = {}, expected {}", n, energy, expected);
+        }
+
+        // The initial state is normalized and survives the evolution that way
+        let norm = |vector: &Vector| vector.inner_product(vector).unwrap().real;
+        assert!((norm(experiment.wavefunction()) - 1.0).abs() < 1e-12);
+        assert!((norm(&experiment.evolve(50.0).unwrap()) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn overflowing_initial_states_are_errors() {
+        let grid = Grid::new(-4.0, 4.0, 4).unwrap();
+        let gaussian = InitialState::parse("exp(-x^2)").unwrap();
+        assert!(gaussian.sample(&grid, 1.0).is_ok());
+        assert!(gaussian.sample(&grid, f64::MAX).is_err());
+
+        // Huge samples still normalize
+        let huge = InitialState::Samples(grid.positions().map(|x| (x, 1e300)).collect());
+        let sampled = huge.sample(&grid, 0.0).unwrap();
+        assert!(sampled.iter().all(|value| (*value - Complex::from(0.5)).modulus() < 1e-15));
+    }
+}
@@ -0,0 +1,478 @@
+use crate::experiment::{Experiment, Grid};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::{
+    fs,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+/// File formats results can be exported to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// Comma separated, with the metadata in `#` comment lines on top
+    Csv,
+    /// A 2D float64 array with one column per field. The format has no room for
+    /// anything else, so the metadata goes to a `.json` file next to it
+    Npy,
+    /// One array per column, plus the metadata as a string array named `metadata`
+    Npz,
+    /// The metadata on the first line, then one object per row
+    Jsonl,
+}
+impl Format {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "csv" => Ok(Format::Csv),
+            "npy" => Ok(Format::Npy),
+            "npz" => Ok(Format::Npz),
+            "jsonl" => Ok(Format::Jsonl),
+            _ => Err(format!("Unknown format `{}`, expected csv, npy, npz or jsonl", name)),
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Csv => "csv",
+            Format::Npy => "npy",
+            Format::Npz => "npz",
+            Format::Jsonl => "jsonl",
+        }
+    }
+}
+
+/// What the exported numbers are measured in. The Hamiltonian is discretized
+/// with ħ = 2m = 1 and in units of the grid spacing
+pub const UNITS: [(&str, &str); 4] = [
+    ("x", "same as the grid bounds"),
+    ("energy", "ħ²/(2mΔx²), Δx being the grid spacing"),
+    ("t", "ħ per unit of energy"),
+    ("wavefunction", "dimensionless, normalized so that Σ|ψ|² = 1 over the grid"),
+];
+
+/// Named columns of numbers, stored row by row. `n`, the eigenstate index, is
+/// written as an integer where the format allows
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    name: &'static str,
+    columns: &'static [&'static str],
+    values: Vec<f64>,
+}
+impl Table {
+    pub fn potential(experiment: &Experiment) -> Self {
+        let grid = experiment.grid();
+        let values = grid
+            .positions()
+            .zip(experiment.potential().iter())
+            .flat_map(|(x, value)| [x, value.real])
+            .collect();
+        Self { name: "potential", columns: &["x", "potential"], values }
+    }
+
+    pub fn eigenvalues(experiment: &Experiment) -> Self {
+        let values = experiment
+            .eigenvalues()
+            .iter()
+            .enumerate()
+            .flat_map(|(n, energy)| [n as f64, *energy])
+            .collect();
+        Self { name: "eigenvalues", columns: &["n", "energy"], values }
+    }
+
+    pub fn eigenvectors(experiment: &Experiment, indices: &[usize]) -> Result<Self, String> {
+        let grid = experiment.grid();
+        let mut values = Vec::with_capacity(indices.len() * grid.resolution() as usize * 4);
+        for &n in indices {
+            let vector = experiment
+                .eigenvector(n)
+                .ok_or(format!("There is no eigenvector {}, only {}", n, experiment.basis().count()))?;
+            for (x, value) in grid.positions().zip(vector) {
+                values.extend([n as f64, x, value.real, value.imag]);
+            }
+        }
+        Ok(Self { name: "eigenvectors", columns: &["n", "x", "real", "imag"], values })
+    }
+
+    pub fn frames(experiment: &Experiment, times: &[f64]) -> Result<Self, String> {
+        let grid = experiment.grid();
+        let frames = experiment.evolve_many(times)?;
+        let mut values = Vec::with_capacity(times.len() * grid.resolution() as usize * 5);
+        for (time, frame) in times.iter().zip(&frames) {
+            for (x, value) in grid.positions().zip(frame.iter()) {
+                values.extend([*time, x, value.real, value.imag, value.modulus_squared()]);
+            }
+        }
+        Ok(Self { name: "frames", columns: &["t", "x", "real", "imag", "density"], values })
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn columns(&self) -> &'static [&'static str] {
+        self.columns
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[f64]> + '_ {
+        self.values.chunks(self.columns.len())
+    }
+
+    pub fn row_count(&self) -> usize {
+        self.values.len() / self.columns.len()
+    }
+
+    fn column(&self, index: usize) -> Vec<f64> {
+        self.rows().map(|row| row[index]).collect()
+    }
+
+    /// The grid, units and columns, as written next to the numbers
+    pub fn metadata(&self, grid: &Grid) -> Value {
+        json!({
+            "table": self.name,
+            "columns": self.columns,
+            "grid": grid,
+            "spacing": grid.spacing(),
+            "units": UNITS
+                .iter()
+                .map(|(quantity, unit)| (quantity.to_string(), json!(unit)))
+                .collect::<Map<String, Value>>(),
+        })
+    }
+
+    /// Writes the table to `directory/<name>.<extension>` and returns the path
+    pub fn write(&self, directory: &Path, format: Format, grid: &Grid) -> Result<PathBuf, String> {
+        let path = directory.join(self.name).with_extension(format.extension());
+        let metadata = self.metadata(grid);
+        let result = match format {
+            Format::Csv => self.write_csv(&path, &metadata),
+            Format::Jsonl => self.write_jsonl(&path, &metadata),
+            Format::Npy => self.write_npy(&path, &metadata),
+            Format::Npz => self.write_npz(&path, &metadata),
+        };
+        result.map_err(|error| format!("{}: {}", path.display(), error))?;
+        Ok(path)
+    }
+
+    fn write_csv(&self, path: &Path, metadata: &Value) -> io::Result<()> {
+        let mut file = BufWriter::new(fs::File::create(path)?);
+        writeln!(file, "# {}", metadata)?;
+        writeln!(file, "{}", self.columns.join(","))?;
+        for row in self.rows() {
+            let row: Vec<String> = self
+                .columns
+                .iter()
+                .zip(row)
+                .map(|(column, value)| match *column {
+                    "n" => (*value as usize).to_string(),
+                    _ => format!("{:?}", value),
+                })
+                .collect();
+            writeln!(file, "{}", row.join(","))?;
+        }
+        file.flush()
+    }
+
+    fn write_jsonl(&self, path: &Path, metadata: &Value) -> io::Result<()> {
+        let mut file = BufWriter::new(fs::File::create(path)?);
+        writeln!(file, "{}", json!({ "metadata": metadata }))?;
+        for row in self.rows() {
+            let row: Map<String, Value> = self
+                .columns
+                .iter()
+                .zip(row)
+                .map(|(column, value)| match *column {
+                    "n" => (column.to_string(), json!(*value as usize)),
+                    _ => (column.to_string(), json!(value)),
+                })
+                .collect();
+            writeln!(file, "{}", Value::Object(row))?;
+        }
+        file.flush()
+    }
+
+    fn write_npy(&self, path: &Path, metadata: &Value) -> io::Result<()> {
+        let mut file = BufWriter::new(fs::File::create(path)?);
+        file.write_all(&npy_float64(&self.values, &[self.row_count(), self.columns.len()]))?;
+        file.flush()?;
+        let metadata = serde_json::to_string_pretty(metadata).expect("metadata always serializes");
+        fs::write(path.with_extension("json"), metadata)
+    }
+
+    fn write_npz(&self, path: &Path, metadata: &Value) -> io::Result<()> {
+        let mut entries: Vec<(String, Vec<u8>)> = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(index, column)| {
+                (format!("{}.npy", column), npy_float64(&self.column(index), &[self.row_count()]))
+            })
+            .collect();
+        entries.push(("metadata.npy".into(), npy_string(&metadata.to_string())));
+        let mut file = BufWriter::new(fs::File::create(path)?);
+        write_zip(&mut file, &entries)?;
+        file.flush()
+    }
+}
+
+/// Writes every table for an experiment into `directory`, which is created if needed
+pub fn export(
+    experiment: &Experiment,
+    directory: &Path,
+    format: Format,
+    eigenvectors: &[usize],
+    times: &[f64],
+) -> Result<Vec<PathBuf>, String> {
+    fs::create_dir_all(directory).map_err(|error| format!("{}: {}", directory.display(), error))?;
+    let tables = [
+        Table::potential(experiment),
+        Table::eigenvalues(experiment),
+        Table::eigenvectors(experiment, eigenvectors)?,
+        Table::frames(experiment, times)?,
+    ];
+    tables.iter().map(|table| table.write(directory, format, experiment.grid())).collect()
+}
+
+/// https://numpy.org/doc/stable/reference/generated/numpy.lib.format.html
+fn npy(descr: &str, shape: &[usize], data: &[u8]) -> Vec<u8> {
+    let shape = match shape {
+        [length] => format!("({},)", length),
+        _ => format!("({})", shape.iter().map(usize::to_string).collect::<Vec<_>>().join(", ")),
+    };
+    let mut header = format!("{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}", descr, shape);
+    // Magic, version and header length take 10 bytes, and the data starts 64 byte aligned
+    let padding = 63 - (10 + header.len()) % 64;
+    header.extend(std::iter::repeat(' ').take(padding));
+    header.push('\n');
+
+    let mut bytes = Vec::with_capacity(10 + header.len() + data.len());
+    bytes.extend(b"\x93NUMPY\x01\x00");
+    bytes.extend((header.len() as u16).to_le_bytes());
+    bytes.extend(header.as_bytes());
+    bytes.extend(data);
+    bytes
+}
+
+fn npy_float64(values: &[f64], shape: &[usize]) -> Vec<u8> {
+    let data: Vec<u8> = values.iter().flat_map(|value| value.to_le_bytes()).collect();
+    npy("<f8", shape, &data)
+}
+
+/// A zero dimensional numpy unicode array, which numpy stores as UTF-32
+fn npy_string(text: &str) -> Vec<u8> {
+    let data: Vec<u8> = text.chars().flat_map(|c| (c as u32).to_le_bytes()).collect();
+    npy(&format!("<U{}", text.chars().count()), &[], &data)
+}
+
+/// An uncompressed zip archive, which is all `numpy.load` needs to read a `.npz`
+/// https://pkware.cachefly.net/webdocs/casestudies/APPNOTE.TXT
+fn write_zip(file: &mut impl Write, entries: &[(String, Vec<u8>)]) -> io::Result<()> {
+    let too_large = || io::Error::new(io::ErrorKind::Other, "Export is too large for a zip archive");
+    let size = |length: usize| u32::try_from(length).map_err(|_| too_large());
+    // Version 2.0, no flags, stored, and 1980-01-01 00:00 as the modification time
+    let common = |crc: u32, length: u32, name: &str| {
+        let mut bytes = vec![];
+        bytes.extend(20u16.to_le_bytes());
+        bytes.extend(0u16.to_le_bytes());
+        bytes.extend(0u16.to_le_bytes());
+        bytes.extend(0u16.to_le_bytes());
+        bytes.extend(0x21u16.to_le_bytes());
+        bytes.extend(crc.to_le_bytes());
+        bytes.extend(length.to_le_bytes());
+        bytes.extend(length.to_le_bytes());
+        bytes.extend((name.len() as u16).to_le_bytes());
+        bytes.extend(0u16.to_le_bytes());
+        bytes
+    };
+
+    let mut offset = 0;
+    let mut directory = vec![];
+    for (name, data) in entries {
+        let crc = crc32fast::hash(data);
+        let length = size(data.len())?;
+        let header = common(crc, length, name);
+
+        directory.extend(0x02014b50u32.to_le_bytes());
+        directory.extend(20u16.to_le_bytes());
+        directory.extend(&header);
+        // Comment length, disk, internal and external attributes
+        directory.extend([0; 10]);
+        directory.extend(size(offset)?.to_le_bytes());
+        directory.extend(name.as_bytes());
+
+        file.write_all(&0x04034b50u32.to_le_bytes())?;
+        file.write_all(&header)?;
+        file.write_all(name.as_bytes())?;
+        file.write_all(data)?;
+        offset += 30 + name.len() + data.len();
+    }
+
+    file.write_all(&directory)?;
+    file.write_all(&0x06054b50u32.to_le_bytes())?;
+    file.write_all(&[0; 4])?;
+    file.write_all(&(entries.len() as u16).to_le_bytes())?;
+    file.write_all(&(entries.len() as u16).to_le_bytes())?;
+    file.write_all(&size(directory.len())?.to_le_bytes())?;
+    file.write_all(&size(offset)?.to_le_bytes())?;
+    file.write_all(&0u16.to_le_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import;
+
+    fn grid() -> Grid {
+        Grid::new(-1.0, 1.0, 3).unwrap()
+    }
+
+    /// Values that only survive exact round trips
+    fn potential() -> Table {
+        let values = vec![-1.0, 0.1, -1.0 / 3.0, 1e-300, 1.0 / 3.0, -2.5e10];
+        Table { name: "potential", columns: &["x", "potential"], values }
+    }
+
+    fn eigenvectors() -> Table {
+        let values = vec![0.0, -1.0, 0.5, -0.25, 0.0, 0.0, 0.1, 0.2, 3.0, 1.0, -0.7, 0.0];
+        Table { name: "eigenvectors", columns: &["n", "x", "real", "imag"], values }
+    }
+
+    /// A scratch directory, removed again by the caller
+    fn directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("qsim-export-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    /// The header and the data of a `.npy` file
+    fn split_npy(bytes: &[u8]) -> (&str, &[u8]) {
+        assert_eq!(&bytes[..8], b"\x93NUMPY\x01\x00");
+        let length = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        (std::str::from_utf8(&bytes[10..10 + length]).unwrap(), &bytes[10 + length..])
+    }
+
+    fn float64(data: &[u8]) -> Vec<f64> {
+        data.chunks(8).map(|chunk| f64::from_le_bytes(chunk.try_into().unwrap())).collect()
+    }
+
+    fn utf32(data: &[u8]) -> String {
+        data.chunks(4).map(|chunk| char::from_u32(u32::from_le_bytes(chunk.try_into().unwrap())).unwrap()).collect()
+    }
+
+    /// Entries of a zip archive, found through its central directory as unzip tools do
+    fn read_zip(bytes: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let u16_at = |at: usize| u16::from_le_bytes([bytes[at], bytes[at + 1]]) as usize;
+        let u32_at = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+        let end = bytes.len() - 22;
+        assert_eq!(u32_at(end), 0x06054b50);
+        assert_eq!(u16_at(end + 8), u16_at(end + 10));
+        let size = u32_at(end + 12) as usize;
+        let mut at = u32_at(end + 16) as usize;
+        assert_eq!(at + size, end);
+
+        let mut entries = vec![];
+        for _ in 0..u16_at(end + 10) {
+            assert_eq!(u32_at(at), 0x02014b50);
+            assert_eq!(u16_at(at + 10), 0, "stored without compression");
+            let (crc, length) = (u32_at(at + 16), u32_at(at + 20) as usize);
+            assert_eq!(u32_at(at + 24) as usize, length);
+            let name_length = u16_at(at + 28);
+            let name = &bytes[at + 46..at + 46 + name_length];
+
+            // The local header repeats the directory's fields and the name
+            let offset = u32_at(at + 42) as usize;
+            assert_eq!(u32_at(offset), 0x04034b50);
+            assert_eq!(bytes[offset + 4..offset + 30], bytes[at + 6..at + 32]);
+            assert_eq!(&bytes[offset + 30..offset + 30 + name_length], name);
+            let data = &bytes[offset + 30 + name_length..offset + 30 + name_length + length];
+            assert_eq!(crc32fast::hash(data), crc);
+
+            entries.push((String::from_utf8(name.to_vec()).unwrap(), data.to_vec()));
+            at += 46 + name_length + u16_at(at + 30) + u16_at(at + 32);
+        }
+        assert_eq!(at, end);
+        entries
+    }
+
+    #[test]
+    fn npy_data_is_aligned() {
+        for shape in [&[][..], &[3], &[3, 4], &[123_456_789, 5]] {
+            let bytes = npy_float64(&[0.5; 12], shape);
+            let (header, data) = split_npy(&bytes);
+            assert_eq!((10 + header.len()) % 64, 0, "{}", header);
+            assert!(header.ends_with(" \n") || header.ends_with("}\n"));
+            assert_eq!(float64(data), vec![0.5; 12]);
+        }
+        assert!(split_npy(&npy_float64(&[], &[3])).0.contains("'shape': (3,)"));
+        assert!(split_npy(&npy_float64(&[], &[3, 4])).0.contains("'shape': (3, 4)"));
+        assert!(split_npy(&npy_float64(&[], &[])).0.contains("'shape': ()"));
+
+        let table = potential();
+        let bytes = npy_float64(&table.values, &[table.row_count(), 2]);
+        assert_eq!(import::parse_npy(&bytes).unwrap(), vec![(-1.0, 0.1), (-1.0 / 3.0, 1e-300), (1.0 / 3.0, -2.5e10)]);
+    }
+
+    #[test]
+    fn npy_strings_are_utf32() {
+        let text = "ħ²/(2mΔx²) Σ|ψ|²";
+        let bytes = npy_string(text);
+        let (header, data) = split_npy(&bytes);
+        assert!(header.contains(&format!("'descr': '<U{}'", text.chars().count())), "{}", header);
+        assert!(header.contains("'shape': ()"));
+        assert_eq!((10 + header.len()) % 64, 0);
+        assert_eq!(utf32(data), text);
+    }
+
+    #[test]
+    fn npz_archives_hold_every_column() {
+        let directory = directory("npz");
+        let table = eigenvectors();
+        let path = table.write(&directory, Format::Npz, &grid()).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+
+        let entries = read_zip(&bytes);
+        let names: Vec<&str> = entries.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["n.npy", "x.npy", "real.npy", "imag.npy", "metadata.npy"]);
+        for (index, (_, data)) in entries[..4].iter().enumerate() {
+            let (header, data) = split_npy(data);
+            assert!(header.contains("'shape': (3,)"));
+            assert_eq!(float64(data), table.column(index));
+        }
+        let metadata = utf32(split_npy(&entries[4].1).1);
+        assert_eq!(serde_json::from_str::<Value>(&metadata).unwrap(), table.metadata(&grid()));
+
+        let mut empty = vec![];
+        write_zip(&mut empty, &[]).unwrap();
+        assert!(read_zip(&empty).is_empty());
+    }
+
+    #[test]
+    fn csv_reads_back_through_the_importer() {
+        let directory = directory("csv");
+        let path = potential().write(&directory, Format::Csv, &grid()).unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        let points = import::read(&path);
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert!(text.starts_with("# {"));
+        assert_eq!(text.lines().nth(1), Some("x,potential"));
+        assert_eq!(points.unwrap(), vec![(-1.0, 0.1), (-1.0 / 3.0, 1e-300), (1.0 / 3.0, -2.5e10)]);
+        assert_eq!(import::parse_text(&text).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn jsonl_starts_with_the_metadata() {
+        let directory = directory("jsonl");
+        let table = eigenvectors();
+        let path = table.write(&directory, Format::Jsonl, &grid()).unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+
+        let lines: Vec<Value> = text.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(lines[0], json!({ "metadata": table.metadata(&grid()) }));
+        assert_eq!(lines[1], json!({ "n": 0, "x": -1.0, "real": 0.5, "imag": -0.25 }));
+        assert!(lines[3]["n"].is_u64());
+        assert_eq!(lines.len(), 1 + table.row_count());
+    }
+}
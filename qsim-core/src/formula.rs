@@ -0,0 +1,1521 @@
+use super::complex::Complex;
+use nom::{branch, bytes, character, combinator, sequence, IResult};
+use serde::Serialize;
+use std::{collections::HashMap, fmt};
+
+/// Variables that a formula may reference, besides the constants `i` and `e`
+const VARIABLES: [char; 1] = ['x'];
+/// Function names offered as suggestions for unknown identifiers
+const FUNCTION_NAMES: [&str; 8] = ["cos", "sin", "sqrt", "ln", "log", "tan", "sen", "exp"];
+
+/// Byte range of the source formula
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    fn to(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum FormulaErrorKind {
+    InvalidCharacter,
+    UnmatchedBracket,
+    MissingOperand,
+    MissingArgument,
+    UnexpectedToken,
+    UnknownVariable,
+    NumberTooLarge,
+    EmptyExpression,
+    TooLong,
+    TooDeep,
+}
+impl FormulaErrorKind {
+    fn description(&self) -> &'static str {
+        match self {
+            FormulaErrorKind::InvalidCharacter => "Invalid character",
+            FormulaErrorKind::UnmatchedBracket => "Unmatched bracket",
+            FormulaErrorKind::MissingOperand => "Operator is missing an operand",
+            FormulaErrorKind::MissingArgument => "Function is missing an argument",
+            FormulaErrorKind::UnexpectedToken => "Unexpected token",
+            FormulaErrorKind::UnknownVariable => "Unknown variable",
+            FormulaErrorKind::NumberTooLarge => "Number is too large",
+            FormulaErrorKind::EmptyExpression => "Empty expression",
+            FormulaErrorKind::TooLong => "Formula is too long",
+            FormulaErrorKind::TooDeep => "Formula is nested too deeply",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FormulaError {
+    pub kind: FormulaErrorKind,
+    pub span: Span,
+    pub expected: Vec<String>,
+    pub suggestions: Vec<String>,
+    pub message: String,
+}
+impl FormulaError {
+    fn new(kind: FormulaErrorKind, span: Span) -> Self {
+        Self {
+            kind,
+            span,
+            expected: vec![],
+            suggestions: vec![],
+            message: kind.description().into(),
+        }
+    }
+
+    fn expecting(mut self, expected: &[&str]) -> Self {
+        self.expected = expected.iter().map(|e| e.to_string()).collect();
+        self
+    }
+
+    fn suggesting(mut self, suggestions: Vec<String>) -> Self {
+        self.suggestions = suggestions;
+        self
+    }
+
+    fn operand_expected(span: Span) -> Self {
+        Self::new(FormulaErrorKind::MissingOperand, span).expecting(&["number", "variable", "("])
+    }
+}
+impl fmt::Display for FormulaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}..{}", self.message, self.span.start, self.span.end)?;
+        if !self.expected.is_empty() {
+            write!(f, ", expected {}", self.expected.join(" or "))?;
+        }
+        if !self.suggestions.is_empty() {
+            let suggestions = self
+                .suggestions
+                .iter()
+                .map(|s| format!("`{}`", s))
+                .collect::<Vec<String>>();
+            write!(f, ", did you mean {}?", suggestions.join(" or "))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operator {
+    Addition,
+    Subtraction,
+    Multiplication,
+    Division,
+    Exponentiation,
+}
+impl Operator {
+    /// Left and right binding powers, a higher left power makes `^` right associative
+    fn binding_power(&self) -> (u8, u8) {
+        match self {
+            Operator::Addition | Operator::Subtraction => (1, 2),
+            Operator::Multiplication | Operator::Division => (3, 4),
+            Operator::Exponentiation => (10, 9),
+        }
+    }
+}
+/// Right binding power of unary `-` and `+`, so `-x^2` is `-(x^2)` but `-2*x` is `(-2)*x`
+const PREFIX_POWER: u8 = 5;
+/// Binding powers of implicit multiplication, tighter than `/` so `1/2x` is `1/(2x)`
+const JUXTAPOSITION_POWER: (u8, u8) = (7, 8);
+/// Parsing, evaluating and printing all recurse over the tree, so these keep its
+/// depth far from what a command thread's stack can take
+const MAX_TOKENS: usize = 1000;
+const MAX_DEPTH: usize = 100;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Function {
+    Cos,
+    Sin,
+    Ln,
+    Sqrt,
+    Tan,
+    Step,
+    // Delta,
+    Exp,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct FunctionCall {
+    function: Function,
+    argument: Box<Node>
+}
+impl FunctionCall {
+    fn evaluate(&self, input: Complex) -> Result<Complex, String> {
+        let arg = self.argument.evaluate(input)?;
+        match self.function {
+            Function::Cos => Ok(arg.cos()),
+            Function::Sin => Ok(arg.sin()),
+            Function::Ln => Ok(arg.ln()),
+            Function::Exp => Ok(arg.exp()),
+            // Function::Delta => Ok(arg.delta()),
+            Function::Sqrt => Ok(arg.sqrt()),
+            Function::Tan => Ok(arg.tan()),
+            Function::Step => Ok(arg.step()),
+        }
+    }
+
+    fn evaluate_multivariable(&self, variables: &HashMap<char, Complex>) -> Result<Complex, String> {
+        let arg = self.argument.evaluate_multivariable(variables)?;
+        match self.function {
+            Function::Cos => Ok(arg.cos()),
+            Function::Sin => Ok(arg.sin()),
+            Function::Ln => Ok(arg.ln()),
+            Function::Exp => Ok(arg.exp()),
+            // Function::Delta => Ok(arg.delta()),
+            Function::Sqrt => Ok(arg.sqrt()),
+            Function::Tan => Ok(arg.tan()),
+            Function::Step => Ok(arg.step()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Operation {
+    operator: Operator,
+    left: Box<Node>,
+    right: Box<Node>,
+}
+impl Operation {
+    fn new(operator: Operator, left: Node, right: Node) -> Self {
+        Self {
+            operator,
+            left: Box::new(left),
+            right: Box::new(right),
+        }
+    }
+
+    fn evaluate(&self, input: Complex) -> Result<Complex, String> {
+        let left = self.left.evaluate(input)?;
+        let right = self.right.evaluate(input)?;
+
+        match self.operator {
+            Operator::Addition => Ok(left + right),
+            Operator::Subtraction => Ok(left - right),
+            Operator::Multiplication => Ok(left * right),
+            Operator::Division => {
+                if right.is_zero() {
+                    Err("Division by zero".into())
+                } else {
+                    Ok(left / right)
+                }
+            }
+            Operator::Exponentiation => Ok(left.powf(&right)),
+        }
+    }
+
+    fn evaluate_multivariable(
+        &self,
+        variables: &HashMap<char, Complex>,
+    ) -> Result<Complex, String> {
+        let left = self.left.evaluate_multivariable(variables)?;
+        let right = self.right.evaluate_multivariable(variables)?;
+
+        match self.operator {
+            Operator::Addition => Ok(left + right),
+            Operator::Subtraction => Ok(left - right),
+            Operator::Multiplication => Ok(left * right),
+            Operator::Division => {
+                if right.is_zero() {
+                    Err("Division by zero".into())
+                } else {
+                    Ok(left / right)
+                }
+            }
+            Operator::Exponentiation => Ok(left.powf(&right)),
+        }
+    }
+}
+
+/// Reserved names, which evaluate the same regardless of the bound variables
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Constant {
+    ImaginaryUnit,
+    E,
+}
+impl Constant {
+    fn value(&self) -> Complex {
+        match self {
+            Constant::ImaginaryUnit => Complex::iunit(),
+            Constant::E => Complex::from(std::f64::consts::E),
+        }
+    }
+
+    fn symbol(&self) -> char {
+        match self {
+            Constant::ImaginaryUnit => 'i',
+            Constant::E => 'e',
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Value(Complex),
+    Constant(Constant),
+    Variable(char),
+    Negation(Box<Node>),
+    Operation(Operation),
+    Function(FunctionCall),
+}
+impl Node {
+    fn evaluate(&self, input: Complex) -> Result<Complex, String> {
+        match &self {
+            Node::Value(value) => Ok(*value),
+            Node::Constant(constant) => Ok(constant.value()),
+            Node::Variable(_) => Ok(input),
+            Node::Negation(operand) => Ok(-operand.evaluate(input)?),
+            Node::Operation(operation) => operation.evaluate(input),
+            Node::Function(function) => function.evaluate(input),
+        }
+    }
+
+    fn evaluate_multivariable(
+        &self,
+        variables: &HashMap<char, Complex>,
+    ) -> Result<Complex, String> {
+        match &self {
+            Node::Value(value) => Ok(*value),
+            Node::Constant(constant) => Ok(constant.value()),
+            Node::Variable(variable) => match variables.get(variable) {
+                None => Err(format!("Missing value for variable {}", variable)),
+                Some(value) => Ok(*value),
+            },
+            Node::Negation(operand) => Ok(-operand.evaluate_multivariable(variables)?),
+            Node::Operation(operation) => operation.evaluate_multivariable(variables),
+            Node::Function(function) => function.evaluate_multivariable(variables),
+        }
+    }
+}
+
+fn constant(value: f64) -> Node {
+    Node::Value(Complex::from(value))
+}
+
+fn operation(operator: Operator, left: Node, right: Node) -> Node {
+    Node::Operation(Operation::new(operator, left, right))
+}
+
+fn function_call(function: Function, argument: Node) -> Node {
+    Node::Function(FunctionCall { function, argument: Box::new(argument) })
+}
+
+impl Node {
+    fn depends_on(&self, variable: char) -> bool {
+        match self {
+            Node::Value(_) | Node::Constant(_) => false,
+            Node::Variable(other) => *other == variable,
+            Node::Negation(operand) => operand.depends_on(variable),
+            Node::Operation(operation) => {
+                operation.left.depends_on(variable) || operation.right.depends_on(variable)
+            }
+            Node::Function(call) => call.argument.depends_on(variable),
+        }
+    }
+
+    /// Unsimplified derivative with respect to `variable`
+    fn derivative(&self, variable: char) -> Node {
+        if !self.depends_on(variable) {
+            return constant(0.0);
+        }
+
+        match self {
+            Node::Value(_) | Node::Constant(_) => constant(0.0),
+            Node::Variable(_) => constant(1.0),
+            Node::Negation(operand) => Node::Negation(Box::new(operand.derivative(variable))),
+            Node::Operation(Operation { operator, left, right }) => {
+                let (u, v) = (left.as_ref().clone(), right.as_ref().clone());
+                let (du, dv) = (left.derivative(variable), right.derivative(variable));
+                match operator {
+                    Operator::Addition | Operator::Subtraction => operation(operator.clone(), du, dv),
+                    Operator::Multiplication => operation(
+                        Operator::Addition,
+                        operation(Operator::Multiplication, du, v),
+                        operation(Operator::Multiplication, u, dv),
+                    ),
+                    Operator::Division => operation(
+                        Operator::Division,
+                        operation(
+                            Operator::Subtraction,
+                            operation(Operator::Multiplication, du, v.clone()),
+                            operation(Operator::Multiplication, u, dv),
+                        ),
+                        operation(Operator::Exponentiation, v, constant(2.0)),
+                    ),
+                    // d(u^v) = v u^(v-1) du when the exponent is constant
+                    Operator::Exponentiation if !right.depends_on(variable) => operation(
+                        Operator::Multiplication,
+                        operation(
+                            Operator::Multiplication,
+                            v.clone(),
+                            operation(
+                                Operator::Exponentiation,
+                                u,
+                                operation(Operator::Subtraction, v, constant(1.0)),
+                            ),
+                        ),
+                        du,
+                    ),
+                    // d(u^v) = u^v (dv ln u + v du / u)
+                    Operator::Exponentiation => operation(
+                        Operator::Multiplication,
+                        self.clone(),
+                        operation(
+                            Operator::Addition,
+                            operation(Operator::Multiplication, dv, function_call(Function::Ln, u.clone())),
+                            operation(Operator::Division, operation(Operator::Multiplication, v, du), u),
+                        ),
+                    ),
+                }
+            }
+            Node::Function(FunctionCall { function, argument }) => {
+                let u = argument.as_ref().clone();
+                let outer = match function {
+                    Function::Cos => Node::Negation(Box::new(function_call(Function::Sin, u))),
+                    Function::Sin => function_call(Function::Cos, u),
+                    Function::Ln => operation(Operator::Division, constant(1.0), u),
+                    Function::Exp => self.clone(),
+                    Function::Sqrt => operation(
+                        Operator::Division,
+                        constant(0.5),
+                        self.clone(),
+                    ),
+                    Function::Tan => operation(
+                        Operator::Division,
+                        constant(1.0),
+                        operation(Operator::Exponentiation, function_call(Function::Cos, u), constant(2.0)),
+                    ),
+                    // A delta at the jump, which has no representation
+                    Function::Step => constant(0.0),
+                };
+                operation(Operator::Multiplication, outer, argument.derivative(variable))
+            }
+        }
+    }
+
+    /// Folds constants, also across the factors of a product or quotient, removes
+    /// neutral elements and cancels factors shared by a numerator and denominator,
+    /// bottom up
+    fn simplify(self) -> Node {
+        match self {
+            Node::Value(_) | Node::Constant(_) | Node::Variable(_) => self,
+            Node::Negation(operand) => match operand.simplify() {
+                Node::Value(value) => Node::Value(-value),
+                Node::Negation(inner) => *inner,
+                operand @ Node::Operation(Operation {
+                    operator: Operator::Multiplication | Operator::Division,
+                    ..
+                }) => Product::of(Node::Negation(Box::new(operand))).into_node(),
+                operand => Node::Negation(Box::new(operand)),
+            },
+            Node::Function(FunctionCall { function, argument }) => match (function, argument.simplify()) {
+                (Function::Ln, Node::Constant(Constant::E)) => constant(1.0),
+                (function, argument) => {
+                    let literal = matches!(argument, Node::Value(_));
+                    let call = function_call(function, argument);
+                    match call.evaluate(Complex::zero()) {
+                        Ok(value) if literal && value.real.is_finite() && value.imag.is_finite() => {
+                            Node::Value(value)
+                        }
+                        _ => call,
+                    }
+                }
+            },
+            Node::Operation(Operation { operator, left, right }) => {
+                let left = left.simplify();
+                let right = right.simplify();
+
+                if let (Node::Value(a), Node::Value(b)) = (&left, &right) {
+                    let folded = match operator {
+                        Operator::Addition => *a + *b,
+                        Operator::Subtraction => *a - *b,
+                        Operator::Multiplication => *a * *b,
+                        Operator::Division => *a / *b,
+                        Operator::Exponentiation => a.powf(b),
+                    };
+                    if folded.real.is_finite() && folded.imag.is_finite() {
+                        return Node::Value(folded);
+                    }
+                }
+
+                let is = |node: &Node, value: f64| matches!(node, Node::Value(v) if *v == Complex::from(value));
+                match operator {
+                    Operator::Addition if is(&left, 0.0) => right,
+                    Operator::Addition | Operator::Subtraction if is(&right, 0.0) => left,
+                    Operator::Subtraction if is(&left, 0.0) => Node::Negation(Box::new(right)).simplify(),
+                    Operator::Addition => match right.without_sign() {
+                        Ok(right) => operation(Operator::Subtraction, left, right),
+                        Err(right) => operation(Operator::Addition, left, right),
+                    },
+                    Operator::Multiplication if is(&left, 0.0) || is(&right, 0.0) => constant(0.0),
+                    Operator::Multiplication if is(&left, 1.0) => right,
+                    Operator::Multiplication | Operator::Division if is(&right, 1.0) => left,
+                    Operator::Division if is(&left, 0.0) => constant(0.0),
+                    Operator::Multiplication | Operator::Division => {
+                        Product::of(operation(operator, left, right)).into_node()
+                    }
+                    Operator::Exponentiation if is(&right, 0.0) => constant(1.0),
+                    Operator::Exponentiation if is(&right, 1.0) => left,
+                    Operator::Subtraction | Operator::Exponentiation => operation(operator, left, right),
+                }
+            }
+        }
+    }
+}
+
+impl Node {
+    /// The node with its leading negation removed, also from the first factor of a product
+    fn without_sign(self) -> Result<Node, Node> {
+        match self {
+            Node::Negation(operand) => Ok(*operand),
+            Node::Operation(Operation {
+                operator: operator @ (Operator::Multiplication | Operator::Division),
+                left,
+                right,
+            }) => match left.without_sign() {
+                Ok(left) => Ok(operation(operator, left, *right)),
+                Err(left) => Err(operation(operator, left, *right)),
+            },
+            node => Err(node),
+        }
+    }
+}
+
+/// A chain of multiplications and divisions, as one coefficient over the
+/// remaining factors
+struct Product {
+    coefficient: Complex,
+    numerator: Vec<Node>,
+    denominator: Vec<Node>,
+}
+impl Product {
+    /// Collects the factors of already simplified operands
+    fn of(node: Node) -> Self {
+        let mut product = Self { coefficient: Complex::from(1.0), numerator: vec![], denominator: vec![] };
+        product.collect(node, true);
+
+        // `x/x` is 1, even though it has no value at 0
+        let mut numerator = vec![];
+        for factor in product.numerator.drain(..) {
+            match product.denominator.iter().position(|other| *other == factor) {
+                Some(index) => {
+                    product.denominator.remove(index);
+                }
+                None => numerator.push(factor),
+            }
+        }
+        product.numerator = numerator;
+        product
+    }
+
+    fn collect(&mut self, node: Node, in_numerator: bool) {
+        match node {
+            Node::Operation(Operation { operator: Operator::Multiplication, left, right }) => {
+                self.collect(*left, in_numerator);
+                self.collect(*right, in_numerator);
+            }
+            Node::Operation(Operation { operator: Operator::Division, left, right }) => {
+                self.collect(*left, in_numerator);
+                self.collect(*right, !in_numerator);
+            }
+            Node::Negation(operand) => {
+                self.coefficient = -self.coefficient;
+                self.collect(*operand, in_numerator);
+            }
+            // A zero divisor stays, so evaluating still reports the division by zero
+            Node::Value(value) if in_numerator || !value.is_zero() => {
+                if in_numerator {
+                    self.coefficient *= value;
+                } else {
+                    self.coefficient /= value;
+                }
+            }
+            factor if in_numerator => self.numerator.push(factor),
+            factor => self.denominator.push(factor),
+        }
+    }
+
+    fn into_node(mut self) -> Node {
+        if self.numerator.is_empty() && self.denominator.is_empty() {
+            return Node::Value(self.coefficient);
+        }
+        let negative = self.coefficient.imag == 0.0 && self.coefficient.real < 0.0;
+        if negative {
+            self.coefficient = -self.coefficient;
+        }
+        // `x/3` reads better than `0.3333333333333333*x`
+        let reciprocal = self.coefficient.inverse();
+        if self.coefficient.real < 1.0 && reciprocal.imag == 0.0 && reciprocal.real.fract() == 0.0 {
+            self.denominator.insert(0, Node::Value(reciprocal));
+        } else if self.coefficient.is_zero() {
+            return constant(0.0);
+        } else if self.coefficient != Complex::from(1.0) {
+            self.numerator.insert(0, Node::Value(self.coefficient));
+        }
+        // On the leading factor, so the product prints as `-3*x` and parses back the same
+        if negative {
+            let leading = if self.numerator.is_empty() { constant(1.0) } else { self.numerator.remove(0) };
+            self.numerator.insert(0, Node::Negation(Box::new(leading)));
+        }
+
+        let chain = |factors: Vec<Node>| {
+            factors.into_iter().reduce(|left, right| operation(Operator::Multiplication, left, right))
+        };
+        let numerator = chain(self.numerator).unwrap_or_else(|| constant(1.0));
+        match chain(self.denominator) {
+            Some(denominator) => operation(Operator::Division, numerator, denominator),
+            None => numerator,
+        }
+    }
+}
+
+impl Function {
+    fn name(&self) -> &'static str {
+        match self {
+            Function::Cos => "cos",
+            Function::Sin => "sin",
+            Function::Ln => "ln",
+            Function::Sqrt => "sqrt",
+            Function::Tan => "tan",
+            Function::Step => "u",
+            Function::Exp => "exp",
+        }
+    }
+
+    fn latex(&self) -> &'static str {
+        match self {
+            Function::Cos => "\\cos",
+            Function::Sin => "\\sin",
+            Function::Ln => "\\ln",
+            Function::Sqrt => "\\sqrt",
+            Function::Tan => "\\tan",
+            Function::Step => "u",
+            Function::Exp => "\\exp",
+        }
+    }
+}
+
+impl Operator {
+    fn symbol(&self) -> &'static str {
+        match self {
+            Operator::Addition => "+",
+            Operator::Subtraction => "-",
+            Operator::Multiplication => "*",
+            Operator::Division => "/",
+            Operator::Exponentiation => "^",
+        }
+    }
+}
+
+/// Printing precedence, mirroring the parser's binding powers
+const SUM_PRECEDENCE: u8 = 1;
+const PRODUCT_PRECEDENCE: u8 = 2;
+const NEGATION_PRECEDENCE: u8 = 3;
+const POWER_PRECEDENCE: u8 = 4;
+const ATOM_PRECEDENCE: u8 = 5;
+
+impl Node {
+    fn precedence(&self) -> u8 {
+        match self {
+            Node::Value(value) if value.real != 0.0 && value.imag != 0.0 => SUM_PRECEDENCE,
+            Node::Value(value) if value.real.is_sign_negative() || value.imag.is_sign_negative() => {
+                NEGATION_PRECEDENCE
+            }
+            // `(2i)^2` needs its brackets, as `2i^2` is `2*i^2`
+            Node::Value(value) if value.imag != 0.0 => POWER_PRECEDENCE,
+            Node::Value(_) | Node::Constant(_) | Node::Variable(_) | Node::Function(_) => ATOM_PRECEDENCE,
+            Node::Negation(_) => NEGATION_PRECEDENCE,
+            Node::Operation(operation) => match operation.operator {
+                Operator::Addition | Operator::Subtraction => SUM_PRECEDENCE,
+                Operator::Multiplication | Operator::Division => PRODUCT_PRECEDENCE,
+                Operator::Exponentiation => POWER_PRECEDENCE,
+            },
+        }
+    }
+
+    /// Whether the operands of `operation` need brackets to keep the same tree
+    fn needs_brackets(operation: &Operation) -> (bool, bool) {
+        let precedence = Node::Operation(operation.clone()).precedence();
+        let left = operation.left.precedence();
+        let right = operation.right.precedence();
+        // `x - -x` and `2*-3` parse, but read better bracketed
+        let negated = right == NEGATION_PRECEDENCE;
+        match operation.operator {
+            // Regrouping `x + (y + z)` would round differently
+            Operator::Addition | Operator::Subtraction | Operator::Multiplication | Operator::Division => {
+                (left < precedence, right <= precedence || negated)
+            }
+            Operator::Exponentiation => (left <= precedence, right < precedence),
+        }
+    }
+
+    fn to_latex(&self) -> String {
+        let bracket = |node: &Node, needed: bool| {
+            if needed {
+                format!("\\left({}\\right)", node.to_latex())
+            } else {
+                node.to_latex()
+            }
+        };
+
+        match self {
+            Node::Value(value) => format_value(*value),
+            Node::Constant(constant) => constant.symbol().to_string(),
+            Node::Variable(variable) => variable.to_string(),
+            Node::Negation(operand) => {
+                format!("-{}", bracket(operand, operand.precedence() <= PRODUCT_PRECEDENCE))
+            }
+            Node::Function(FunctionCall { function: Function::Sqrt, argument }) => {
+                format!("\\sqrt{{{}}}", argument.to_latex())
+            }
+            Node::Function(FunctionCall { function, argument }) => {
+                format!("{}{}", function.latex(), bracket(argument, true))
+            }
+            Node::Operation(operation) => {
+                let (left, right) = Node::needs_brackets(operation);
+                match operation.operator {
+                    Operator::Division => format!(
+                        "\\frac{{{}}}{{{}}}",
+                        operation.left.to_latex(),
+                        operation.right.to_latex()
+                    ),
+                    Operator::Exponentiation => format!(
+                        "{{{}}}^{{{}}}",
+                        bracket(&operation.left, left),
+                        operation.right.to_latex()
+                    ),
+                    Operator::Multiplication => format!(
+                        "{} \\cdot {}",
+                        bracket(&operation.left, left),
+                        bracket(&operation.right, right)
+                    ),
+                    _ => format!(
+                        "{} {} {}",
+                        bracket(&operation.left, left),
+                        operation.operator.symbol(),
+                        bracket(&operation.right, right)
+                    ),
+                }
+            }
+        }
+    }
+}
+/// Writes literals the way they are typed, `2`, `3i` or `2 - 3i`
+fn format_value(value: Complex) -> String {
+    if value.imag == 0.0 {
+        value.real.to_string()
+    } else if value.real == 0.0 {
+        format!("{}i", value.imag)
+    } else if value.imag.is_sign_negative() {
+        format!("{} - {}i", value.real, -value.imag)
+    } else {
+        format!("{} + {}i", value.real, value.imag)
+    }
+}
+
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bracket = |f: &mut fmt::Formatter<'_>, node: &Node, needed: bool| {
+            if needed {
+                write!(f, "({})", node)
+            } else {
+                write!(f, "{}", node)
+            }
+        };
+
+        match self {
+            Node::Value(value) => write!(f, "{}", format_value(*value)),
+            Node::Constant(constant) => write!(f, "{}", constant.symbol()),
+            Node::Variable(variable) => write!(f, "{}", variable),
+            Node::Negation(operand) => {
+                write!(f, "-")?;
+                // `-2*x` parses as `(-2)*x`, whose zero imaginary part has the other sign
+                bracket(f, operand, operand.precedence() <= PRODUCT_PRECEDENCE)
+            }
+            Node::Function(call) => write!(f, "{}({})", call.function.name(), call.argument),
+            Node::Operation(operation) => {
+                let (left, right) = Node::needs_brackets(operation);
+                bracket(f, &operation.left, left)?;
+                match operation.operator {
+                    Operator::Addition | Operator::Subtraction => {
+                        write!(f, " {} ", operation.operator.symbol())?
+                    }
+                    _ => write!(f, "{}", operation.operator.symbol())?,
+                }
+                bracket(f, &operation.right, right)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Formula {
+    root: Node,
+}
+impl Formula {
+    pub fn complex_phase(k: f64) -> Self {
+        Self {
+            root: Node::Operation(Operation {
+                operator: Operator::Exponentiation,
+                left: Box::new(Node::Constant(Constant::E)),
+                right: Box::new(Node::Operation(Operation {
+                    operator: Operator::Multiplication,
+                    left: Box::new(Node::Constant(Constant::ImaginaryUnit)),
+                    right: Box::new(Node::Operation(Operation {
+                        operator: Operator::Multiplication,
+                        left: Box::new(constant(k)),
+                        right: Box::new(Node::Variable('x'))
+                    })),
+                })),
+            }),
+        }
+    }
+
+    /// Parses a formula, from loosest to tightest binding:
+    /// `+ -`, `* /`, unary `-`, implicit multiplication, function application and `^`.
+    /// A function takes a bracketed argument as a whole (`sin(x)^2` is `(sin x)^2`),
+    /// otherwise it takes the following implicit product up to the next function
+    /// (`sin 2x^2 cos x` is `sin(2x^2) cos(x)`).
+    pub fn new(formula: &str) -> Result<Self, FormulaError> {
+        let tokens = tokenize(formula)?;
+        check_variables(formula, &tokens)?;
+        let root_node = Parser::new(tokens, formula.len()).parse()?;
+        Ok(Self { root: root_node })
+    }
+
+    pub fn get_vector(&self, start: f64, end: f64, length: u32) -> Vec<Complex> {
+        let step = (end - start) / (length as f64);
+        let mut values = vec![];
+        for i in 0..length {
+            let x = start + (i as f64) * step;
+            values.push(self.evaluate_complex(Complex::from(x)).unwrap_or(Complex::zero()))
+        }
+        values
+    }
+
+    pub fn adjoin(self, other: Formula, operator: Operator) -> Formula {
+        Self {
+            root: Node::Operation(Operation::new(operator, self.root, other.root)),
+        }
+    }
+
+    /// LaTeX rendering, with every multiplication shown as `\cdot`
+    pub fn to_latex(&self) -> String {
+        self.root.to_latex()
+    }
+
+    /// Symbolic derivative with respect to `variable`, simplified
+    pub fn derivative(&self, variable: char) -> Formula {
+        Self {
+            root: self.root.derivative(variable).simplify(),
+        }
+    }
+
+    pub fn evaluate(&self, input: Complex) -> Result<Complex, String> {
+        self.root.evaluate(input)
+    }
+
+    pub fn evaluate_complex(&self, input: Complex) -> Result<Complex, String> {
+        let variables = HashMap::from([('x', input)]);
+        self.evaluate_multivariable(&variables)
+    }
+
+    pub fn evaluate_multivariable(
+        &self,
+        variables: &HashMap<char, Complex>,
+    ) -> Result<Complex, String> {
+        self.root.evaluate_multivariable(variables)
+    }
+}
+
+/// Canonical text with minimal brackets and every multiplication written as `*`
+impl fmt::Display for Formula {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.root)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Token {
+    Value(Complex),
+    Constant(Constant),
+    Variable(char),
+    Operator(Operator),
+    Function(Function),
+    OpeningBracket,
+    ClosingBracket,
+    Whitespace,
+}
+
+fn function(input: &str) -> IResult<&str, Token> {
+    let (rest, function) = branch::alt((
+        bytes::complete::tag("cos"),
+        bytes::complete::tag("sin"),
+        bytes::complete::tag("sqrt"),
+        bytes::complete::tag("ln"),
+        bytes::complete::tag("log"),
+        bytes::complete::tag("tan"),
+        bytes::complete::tag("sen"),
+        bytes::complete::tag("u"),
+        // bytes::complete::tag("d"),
+        bytes::complete::tag("exp")))(input)?;
+
+    let function = match function {
+        "cos" => Function::Cos,
+        "sin" | "sen" => Function::Sin,
+        "sqrt" => Function::Sqrt,
+        "ln" | "log" => Function::Ln,
+        "tan" => Function::Tan,
+        "u" => Function::Step,
+        // "d" => Function::Delta,
+        "exp" => Function::Exp,
+        _ => unreachable!()
+    };
+
+    Ok((rest, Token::Function(function)))
+}
+fn bracket(input: &str) -> IResult<&str, Token> {
+    let (rest, bracket) = character::complete::one_of("()")(input)?;
+    let token = match bracket {
+        '(' => Token::OpeningBracket,
+        ')' => Token::ClosingBracket,
+        _ => unreachable!(),
+    };
+    Ok((rest, token))
+}
+fn operator(input: &str) -> IResult<&str, Token> {
+    let (rest, operator) = character::complete::one_of("+-*/^")(input)?;
+    let operator = match operator {
+        '+' => Operator::Addition,
+        '-' => Operator::Subtraction,
+        '*' => Operator::Multiplication,
+        '/' => Operator::Division,
+        '^' => Operator::Exponentiation,
+        _ => unreachable!(),
+    };
+    Ok((rest, Token::Operator(operator)))
+}
+fn variable(input: &str) -> IResult<&str, Token> {
+    let (rest, variable) =
+        combinator::verify(character::complete::anychar, |c: &char| c.is_alphabetic())(input)?;
+    let token = match variable {
+        'i' => Token::Constant(Constant::ImaginaryUnit),
+        'e' => Token::Constant(Constant::E),
+        _ => Token::Variable(variable),
+    };
+    Ok((rest, token))
+}
+/// Unsigned decimal literal with optional exponent, and an `i` suffix for imaginary literals.
+/// The exponent is only taken when digits follow, so `2exp(x)` is `2 exp(x)`.
+fn value(input: &str) -> IResult<&str, Token> {
+    use character::complete::{char, digit0, digit1, one_of};
+    let (rest, literal) = combinator::recognize(sequence::tuple((
+        branch::alt((
+            combinator::recognize(sequence::pair(digit1, combinator::opt(sequence::pair(char('.'), digit0)))),
+            combinator::recognize(sequence::pair(char('.'), digit1)),
+        )),
+        combinator::opt(sequence::tuple((one_of("eE"), combinator::opt(one_of("+-")), digit1))),
+    )))(input)?;
+    let (rest, imaginary) = combinator::opt(char('i'))(rest)?;
+
+    // The grammar above only accepts valid floats
+    let value = literal.parse::<f64>().unwrap();
+    let value = if imaginary.is_some() {
+        Complex::new(0.0, value)
+    } else {
+        Complex::from(value)
+    };
+    Ok((rest, Token::Value(value)))
+}
+fn whitespace(input: &str) -> IResult<&str, Token> {
+    let (rest, _) = character::complete::space1(input)?;
+    Ok((rest, Token::Whitespace))
+}
+
+fn token(input: &str) -> IResult<&str, Token> {
+    branch::alt((function, bracket, operator, variable, value, whitespace))(input)
+}
+
+fn tokenize(formula: &str) -> Result<Vec<(Token, Span)>, FormulaError> {
+    let mut tokens = vec![];
+    let mut rest = formula;
+
+    while let Some(next) = rest.chars().next() {
+        let start = formula.len() - rest.len();
+        match token(rest) {
+            Ok((new_rest, token)) => {
+                let span = Span::new(start, formula.len() - new_rest.len());
+                // Literals past f64::MAX parse to infinity
+                if let Token::Value(value) = &token {
+                    if !value.real.is_finite() || !value.imag.is_finite() {
+                        return Err(FormulaError::new(FormulaErrorKind::NumberTooLarge, span));
+                    }
+                }
+                match token {
+                    // `2i^2` is `2*i^2`, like `2x^2`, so the unit leaves the literal before a power
+                    Token::Value(value)
+                        if formula[..span.end].ends_with('i') && new_rest.trim_start().starts_with('^') =>
+                    {
+                        let unit = Span::new(span.end - 1, span.end);
+                        tokens.push((Token::Value(Complex::from(value.imag)), Span::new(span.start, unit.start)));
+                        tokens.push((Token::Constant(Constant::ImaginaryUnit), unit));
+                    }
+                    Token::Whitespace => {}
+                    token => tokens.push((token, span)),
+                }
+                rest = new_rest;
+            }
+            Err(_) => {
+                return Err(FormulaError::new(
+                    FormulaErrorKind::InvalidCharacter,
+                    Span::new(start, start + next.len_utf8()),
+                ))
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Rejects variables that no evaluation path binds, suggesting function names for typos
+fn check_variables(formula: &str, tokens: &[(Token, Span)]) -> Result<(), FormulaError> {
+    for (token, span) in tokens {
+        if let Token::Variable(variable) = token {
+            if VARIABLES.contains(variable) {
+                continue;
+            }
+
+            // Widen to the whole identifier, so "sqr" is compared as a word
+            let start = formula[..span.start]
+                .char_indices()
+                .rev()
+                .take_while(|(_, c)| c.is_alphabetic())
+                .last()
+                .map_or(span.start, |(i, _)| i);
+            let end = formula[span.end..]
+                .char_indices()
+                .find(|(_, c)| !c.is_alphabetic())
+                .map_or(formula.len(), |(i, _)| span.end + i);
+            let word = &formula[start..end];
+
+            let suggestions = FUNCTION_NAMES
+                .iter()
+                .filter(|name| edit_distance(word, name) <= if word.len() > 4 { 2 } else { 1 })
+                .map(|name| name.to_string())
+                .collect::<Vec<String>>();
+            let span = if suggestions.is_empty() {
+                *span
+            } else {
+                Span::new(start, end)
+            };
+
+            let mut error =
+                FormulaError::new(FormulaErrorKind::UnknownVariable, span).suggesting(suggestions);
+            error.expected = VARIABLES
+                .iter()
+                .chain(&[Constant::ImaginaryUnit.symbol(), Constant::E.symbol()])
+                .map(|v| v.to_string())
+                .collect();
+            return Err(error);
+        }
+    }
+    Ok(())
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<char>>();
+    let mut previous = (0..=b.len()).collect::<Vec<usize>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + if ca == *cb { 0 } else { 1 };
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+struct Parser {
+    tokens: Vec<(Token, Span)>,
+    position: usize,
+    length: usize,
+    /// Nested `expression` calls
+    depth: usize,
+}
+impl Parser {
+    fn new(tokens: Vec<(Token, Span)>, length: usize) -> Self {
+        Self { tokens, position: 0, length, depth: 0 }
+    }
+
+    fn parse(mut self) -> Result<Node, FormulaError> {
+        if self.tokens.is_empty() {
+            return Err(FormulaError::new(FormulaErrorKind::EmptyExpression, Span::new(0, self.length))
+                .expecting(&["number", "variable", "("]));
+        }
+        if let Some((_, span)) = self.tokens.get(MAX_TOKENS) {
+            return Err(FormulaError::new(FormulaErrorKind::TooLong, Span::new(span.start, self.length)));
+        }
+        let root = self.expression(0, false)?;
+        match self.next() {
+            None => Ok(root),
+            Some((Token::ClosingBracket, span)) => {
+                Err(FormulaError::new(FormulaErrorKind::UnmatchedBracket, span))
+            }
+            Some((_, span)) => Err(FormulaError::new(FormulaErrorKind::UnexpectedToken, span)),
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+
+    fn next(&mut self) -> Option<(Token, Span)> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    /// Span of the previous token, used when the formula ends too early
+    fn previous_span(&self) -> Span {
+        self.tokens[self.position.min(self.tokens.len()) - 1].1
+    }
+
+    /// Parses operators binding at least as tight as `min_power`.
+    /// `bare_argument` stops implicit multiplication before a function name.
+    fn expression(&mut self, min_power: u8, bare_argument: bool) -> Result<Node, FormulaError> {
+        // Brackets, prefixes, arguments and right operands all recurse through here
+        if self.depth == MAX_DEPTH {
+            let span = self.tokens.get(self.position).map_or_else(|| self.previous_span(), |(_, span)| *span);
+            return Err(FormulaError::new(FormulaErrorKind::TooDeep, span));
+        }
+        self.depth += 1;
+        let node = self.operations(min_power, bare_argument);
+        self.depth -= 1;
+        node
+    }
+
+    fn operations(&mut self, min_power: u8, bare_argument: bool) -> Result<Node, FormulaError> {
+        let mut left = self.operand(min_power, bare_argument)?;
+
+        loop {
+            let (left_power, right_power, operator) = match self.peek() {
+                Some(Token::Operator(operator)) => {
+                    let (left_power, right_power) = operator.binding_power();
+                    (left_power, right_power, Some(operator.clone()))
+                }
+                Some(Token::Function(_)) if bare_argument => break,
+                Some(
+                    Token::Value(_)
+                    | Token::Constant(_)
+                    | Token::Variable(_)
+                    | Token::Function(_)
+                    | Token::OpeningBracket,
+                ) => {
+                    (JUXTAPOSITION_POWER.0, JUXTAPOSITION_POWER.1, None)
+                }
+                _ => break,
+            };
+            if left_power < min_power {
+                break;
+            }
+
+            let operator = match operator {
+                Some(operator) => {
+                    self.next();
+                    operator
+                }
+                None => Operator::Multiplication,
+            };
+            let right = self.expression(right_power, bare_argument)?;
+            left = Node::Operation(Operation::new(operator, left, right));
+        }
+
+        Ok(left)
+    }
+
+    fn operand(&mut self, min_power: u8, bare_argument: bool) -> Result<Node, FormulaError> {
+        let (token, span) = match self.next() {
+            Some(token) => token,
+            None => {
+                return Err(match self.tokens.last() {
+                    Some((Token::OpeningBracket, span)) => {
+                        FormulaError::new(FormulaErrorKind::UnmatchedBracket, *span).expecting(&[")"])
+                    }
+                    _ => FormulaError::operand_expected(self.previous_span()),
+                })
+            }
+        };
+
+        match token {
+            Token::Value(value) => Ok(Node::Value(value)),
+            Token::Constant(constant) => Ok(Node::Constant(constant)),
+            Token::Variable(variable) => Ok(Node::Variable(variable)),
+            Token::Operator(Operator::Subtraction) => Ok(Node::Negation(Box::new(
+                self.expression(PREFIX_POWER.max(min_power), bare_argument)?,
+            ))),
+            Token::Operator(Operator::Addition) => {
+                self.expression(PREFIX_POWER.max(min_power), bare_argument)
+            }
+            Token::Operator(_) => Err(FormulaError::operand_expected(span)),
+            Token::OpeningBracket => {
+                if let Some(Token::ClosingBracket) = self.peek() {
+                    let closing = self.next().unwrap().1;
+                    return Err(FormulaError::new(FormulaErrorKind::EmptyExpression, span.to(closing))
+                        .expecting(&["number", "variable", "("]));
+                }
+                let inner = self.expression(0, false)?;
+                match self.next() {
+                    Some((Token::ClosingBracket, _)) => Ok(inner),
+                    _ => Err(FormulaError::new(FormulaErrorKind::UnmatchedBracket, span).expecting(&[")"])),
+                }
+            }
+            Token::ClosingBracket => Err(FormulaError::operand_expected(self.previous_operator_span(span))),
+            Token::Function(function) => {
+                let argument = match self.peek() {
+                    None | Some(Token::Operator(Operator::Addition | Operator::Multiplication
+                        | Operator::Division | Operator::Exponentiation) | Token::ClosingBracket) => {
+                        return Err(FormulaError::new(FormulaErrorKind::MissingArgument, span)
+                            .expecting(&["number", "variable", "("]))
+                    }
+                    Some(Token::OpeningBracket) => self.operand(0, false)?,
+                    _ => self.expression(JUXTAPOSITION_POWER.0.max(min_power), true)?,
+                };
+                Ok(Node::Function(FunctionCall { function, argument: Box::new(argument) }))
+            }
+            Token::Whitespace => unreachable!(),
+        }
+    }
+
+    /// A closing bracket where an operand belongs blames the operator before it
+    fn previous_operator_span(&self, span: Span) -> Span {
+        match self.tokens.get(self.position.wrapping_sub(2)) {
+            Some((Token::Operator(_), operator_span)) => *operator_span,
+            _ => span,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn evaluate(formula: &str, x: f64) -> Complex {
+        Formula::new(formula)
+            .unwrap_or_else(|error| panic!("`{}` doesn't parse: {}", formula, error))
+            .evaluate_complex(Complex::from(x))
+            .unwrap()
+    }
+
+    /// Checks `formula` against the same formula with every bracket written out
+    fn assert_parses_as(formula: &str, bracketed: &str) {
+        for x in [-1.7, 0.5, 2.0, 3.3] {
+            let (value, expected) = (evaluate(formula, x), evaluate(bracketed, x));
+            assert!(
+                (value - expected).modulus() <= 1e-12 * expected.modulus().max(1.0),
+                "`{}` = {:?} but `{}` = {:?} at x = {}",
+                formula,
+                value,
+                bracketed,
+                expected,
+                x
+            );
+        }
+        assert_eq!(Formula::new(formula).unwrap().to_string(), Formula::new(bracketed).unwrap().to_string());
+    }
+
+    #[test]
+    fn exponentiation_is_right_associative() {
+        assert_eq!(evaluate("2^3^2", 0.0), Complex::from(512.0));
+        assert_parses_as("2^3^2", "2^(3^2)");
+        assert_parses_as("x^2^-1", "x^(2^(-1))");
+    }
+
+    #[test]
+    fn negation_binds_looser_than_exponentiation() {
+        assert_eq!(evaluate("-x^2", 3.0), Complex::from(-9.0));
+        assert_parses_as("-x^2", "-(x^2)");
+        assert_parses_as("-2*x", "(-2)*x");
+        assert_parses_as("--x", "-(-x)");
+    }
+
+    #[test]
+    fn negative_exponents() {
+        assert_eq!(evaluate("2^-1", 0.0), Complex::from(0.5));
+        assert_parses_as("e^-x^2", "e^(-(x^2))");
+        assert_parses_as("2^-x*3", "(2^(-x))*3");
+    }
+
+    #[test]
+    fn implicit_multiplication_binds_tighter_than_division() {
+        assert_eq!(evaluate("1/2x", 4.0), Complex::from(0.125));
+        assert_parses_as("1/2x", "1/(2*x)");
+        assert_parses_as("3x(x+1)", "3*x*(x+1)");
+    }
+
+    #[test]
+    fn implicit_multiplication_next_to_exponentiation() {
+        assert_parses_as("2x^2", "2*(x^2)");
+        assert_parses_as("x^2x", "(x^2)*x");
+        assert_parses_as("(x+1)^2(x-1)", "((x+1)^2)*(x-1)");
+        assert_parses_as("2^x^2", "2^(x^2)");
+    }
+
+    #[test]
+    fn function_arguments() {
+        assert_parses_as("sin x^2", "sin(x^2)");
+        assert_parses_as("sin(x)^2", "(sin(x))^2");
+        assert_parses_as("sin 2x^2 cos x", "sin(2*x^2)*cos(x)");
+    }
+
+    #[test]
+    fn subtraction_and_division_are_left_associative() {
+        assert_eq!(evaluate("8-3-2", 0.0), Complex::from(3.0));
+        assert_eq!(evaluate("16/4/2", 0.0), Complex::from(2.0));
+        assert_eq!(evaluate("8/2*2", 0.0), Complex::from(8.0));
+        assert_parses_as("x-1-x", "(x-1)-x");
+        assert_parses_as("x/2/x", "(x/2)/x");
+        assert_parses_as("1-x+2", "(1-x)+2");
+    }
+
+    #[test]
+    fn deep_nesting_is_an_error() {
+        let nested = |open: &str, close: &str, depth: usize| format!("{}x{}", open.repeat(depth), close.repeat(depth));
+        // Short enough to stay under the token limit
+        for formula in [
+            nested("(", ")", 300),
+            nested("-", "", 300),
+            nested("2^", "", 300),
+            nested("sin(", ")", 300),
+            nested("sin ", "", 300),
+        ] {
+            assert_eq!(Formula::new(&formula).unwrap_err().kind, FormulaErrorKind::TooDeep);
+        }
+        assert!(Formula::new(&nested("(", ")", MAX_DEPTH - 1)).is_ok());
+        assert_eq!(Formula::new(&nested("(", ")", MAX_DEPTH)).unwrap_err().kind, FormulaErrorKind::TooDeep);
+        assert!(Formula::new(&nested("(", ")", 100_000)).is_err());
+    }
+
+    #[test]
+    fn long_formulas_are_an_error() {
+        let error = Formula::new(&"x+".repeat(5000)).unwrap_err();
+        assert_eq!(error.kind, FormulaErrorKind::TooLong);
+        assert_eq!(error.span, Span::new(2 * (MAX_TOKENS / 2), 10000));
+    }
+
+    #[test]
+    fn formulas_at_the_limits_can_be_used() {
+        let long = format!("{}x", "x+".repeat((MAX_TOKENS - 1) / 2));
+        let deep = format!("{}x{}", "(x^".repeat(MAX_DEPTH / 2 - 1), ")".repeat(MAX_DEPTH / 2 - 1));
+        for formula in [long, deep] {
+            let formula = Formula::new(&formula).unwrap();
+            formula.evaluate_complex(Complex::from(0.5)).unwrap();
+            formula.to_latex();
+            formula.derivative('x').evaluate_complex(Complex::from(0.5)).unwrap();
+        }
+    }
+
+    /// Checks the simplified derivative's text, and its values against a central difference
+    fn assert_derivative(formula: &str, expected: &str) {
+        let parsed = Formula::new(formula).unwrap();
+        let derivative = parsed.derivative('x');
+        assert_eq!(derivative.to_string(), expected, "d/dx {}", formula);
+        let h = 1e-5;
+        for x in [0.3, 0.7, 1.3, 2.1] {
+            let at = |x: f64| parsed.evaluate_complex(Complex::from(x)).unwrap();
+            let difference = (at(x + h) - at(x - h)) / (2.0 * h);
+            let value = derivative.evaluate_complex(Complex::from(x)).unwrap();
+            assert!(
+                (value - difference).modulus() <= 1e-6 * difference.modulus().max(1.0),
+                "d/dx {} = {:?} at x = {}, but the difference is {:?}",
+                formula,
+                value,
+                x,
+                difference
+            );
+        }
+    }
+
+    #[test]
+    fn derivative_rules() {
+        assert_derivative("5", "0");
+        assert_derivative("x", "1");
+        assert_derivative("x^3/3", "x^2");
+        assert_derivative("2x^3-x", "6*x^2 - 1");
+        assert_derivative("x sin(x)", "sin(x) + x*cos(x)");
+        assert_derivative("sin(x)/x", "(cos(x)*x - sin(x))/x^2");
+        assert_derivative("sin(x^2)", "2*cos(x^2)*x");
+        assert_derivative("cos(3x)", "-3*sin(3*x)");
+        assert_derivative("e^x", "e^x");
+        assert_derivative("x^x", "x^x*(ln(x) + 1)");
+        assert_derivative("ln(x)", "1/x");
+        assert_derivative("sqrt(x)", "1/(2*sqrt(x))");
+        assert_derivative("tan(x)", "1/cos(x)^2");
+        assert_derivative("x/2", "0.5");
+        assert_derivative("-x/4", "-0.25");
+    }
+
+    #[test]
+    fn simplification_folds_products() {
+        let simplified = |formula: &str| Formula::new(formula).unwrap().root.simplify().to_string();
+        assert_eq!(simplified("2x*3"), "6*x");
+        assert_eq!(simplified("3x/9"), "x/3");
+        assert_eq!(simplified("-2x*-3"), "6*x");
+        assert_eq!(simplified("x*sin(x)/x"), "sin(x)");
+        assert_eq!(simplified("1 + -2x"), "1 - 2*x");
+        assert_eq!(simplified("ln(e) + sin(0)"), "1");
+        // Folding never hides a division by zero
+        assert_eq!(simplified("x/0"), "x/0");
+    }
+
+    fn assert_error(
+        formula: &str,
+        kind: FormulaErrorKind,
+        span: (usize, usize),
+        expected: &[&str],
+        suggestions: &[&str],
+    ) {
+        let error = Formula::new(formula).unwrap_err();
+        assert_eq!(error.kind, kind, "`{}`", formula);
+        assert_eq!(error.span, Span::new(span.0, span.1), "`{}`", formula);
+        assert_eq!(error.expected, expected, "`{}`", formula);
+        assert_eq!(error.suggestions, suggestions, "`{}`", formula);
+    }
+
+    #[test]
+    fn errors_point_at_their_cause() {
+        let operand = ["number", "variable", "("];
+        let variable = ["x", "i", "e"];
+        assert_error("sqr(x)", FormulaErrorKind::UnknownVariable, (0, 3), &variable, &["sqrt"]);
+        assert_error("sqr x", FormulaErrorKind::UnknownVariable, (0, 3), &variable, &["sqrt"]);
+        assert_error("y", FormulaErrorKind::UnknownVariable, (0, 1), &variable, &[]);
+        assert_error("x +", FormulaErrorKind::MissingOperand, (2, 3), &operand, &[]);
+        assert_error("x*/2", FormulaErrorKind::MissingOperand, (2, 3), &operand, &[]);
+        assert_error("(", FormulaErrorKind::UnmatchedBracket, (0, 1), &[")"], &[]);
+        assert_error("x)", FormulaErrorKind::UnmatchedBracket, (1, 2), &[], &[]);
+        assert_error("2 $ 3", FormulaErrorKind::InvalidCharacter, (2, 3), &[], &[]);
+        assert_error("sin", FormulaErrorKind::MissingArgument, (0, 3), &operand, &[]);
+        assert_error("sin()", FormulaErrorKind::EmptyExpression, (3, 5), &operand, &[]);
+        assert_error("", FormulaErrorKind::EmptyExpression, (0, 0), &operand, &[]);
+
+        assert_eq!(
+            Formula::new("sqr(x)").unwrap_err().to_string(),
+            "Unknown variable at 0..3, expected x or i or e, did you mean `sqrt`?"
+        );
+    }
+
+    /// Checks the canonical text and LaTeX of `formula`
+    fn assert_prints_as(formula: &str, text: &str, latex: &str) {
+        let formula = Formula::new(formula).unwrap();
+        assert_eq!(formula.to_string(), text);
+        assert_eq!(formula.to_latex(), latex);
+    }
+
+    #[test]
+    fn printing_keeps_the_needed_brackets() {
+        assert_prints_as("2x^2sin x", "2*x^2*sin(x)", r"2 \cdot {x}^{2} \cdot \sin\left(x\right)");
+        assert_prints_as("-2^2", "-2^2", "-{2}^{2}");
+        assert_prints_as("(-2)^2", "(-2)^2", r"{\left(-2\right)}^{2}");
+        assert_prints_as("x--x", "x - (-x)", r"x - \left(-x\right)");
+        assert_prints_as("1/2x", "1/(2*x)", r"\frac{1}{2 \cdot x}");
+        assert_prints_as("x^-1", "x^(-1)", "{x}^{-1}");
+        assert_prints_as("x-(1-x)", "x - (1 - x)", r"x - \left(1 - x\right)");
+        assert_prints_as("-2*x", "-2*x", r"-2 \cdot x");
+        assert_prints_as("-2x", "-(2*x)", r"-\left(2 \cdot x\right)");
+    }
+
+    #[test]
+    fn overflowing_literals_are_an_error() {
+        for (formula, span) in [("1e400", (0, 5)), ("2+1e400i", (2, 8)), ("x*1234e999", (2, 10))] {
+            let error = Formula::new(formula).unwrap_err();
+            assert_eq!(error.kind, FormulaErrorKind::NumberTooLarge);
+            assert_eq!(error.span, Span::new(span.0, span.1));
+        }
+        assert!(Formula::new("1e308").is_ok());
+        assert!(Formula::new("1e-400").is_ok());
+    }
+
+    /// Formula text from the grammar's pieces, not all of which parse
+    fn source() -> impl Strategy<Value = String> {
+        let leaf = prop_oneof![
+            Just("x"), Just("i"), Just("e"), Just("2"), Just("0.5"), Just("3i"), Just("1e-3"), Just("7")
+        ]
+        .prop_map(String::from);
+        leaf.prop_recursive(4, 32, 2, |inner| {
+            let operator = prop_oneof![Just("+"), Just("-"), Just("*"), Just("/"), Just("^"), Just(" "), Just("")];
+            prop_oneof![
+                (inner.clone(), operator, inner.clone())
+                    .prop_map(|(left, operator, right)| format!("{}{}{}", left, operator, right)),
+                inner.clone().prop_map(|operand| format!("-{}", operand)),
+                inner.clone().prop_map(|operand| format!("({})", operand)),
+                (prop_oneof![Just("sin"), Just("cos"), Just("ln"), Just("sqrt"), Just("exp")], any::<bool>(), inner)
+                    .prop_map(|(function, bracketed, argument)| if bracketed {
+                        format!("{}({})", function, argument)
+                    } else {
+                        format!("{} {}", function, argument)
+                    }),
+            ]
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn printed_formulas_parse_back(source in source()) {
+            if let Ok(formula) = Formula::new(&source) {
+                let text = formula.to_string();
+                let reparsed =
+                    Formula::new(&text).unwrap_or_else(|error| panic!("`{}` doesn't parse: {}", text, error));
+                for x in [-1.3, 0.7, 2.0] {
+                    let x = Complex::from(x);
+                    match (formula.evaluate_complex(x), reparsed.evaluate_complex(x)) {
+                        (Ok(expected), Ok(value)) => {
+                            let finite = |z: Complex| z.real.is_finite() && z.imag.is_finite();
+                            prop_assert!(
+                                !finite(expected) && !finite(value)
+                                    || (expected - value).modulus() <= 1e-9 * expected.modulus().max(1.0),
+                                "`{}` = {:?} but `{}` = {:?} at x = {}", source, expected, text, value, x
+                            );
+                        }
+                        (expected, value) => prop_assert_eq!(expected.is_ok(), value.is_ok()),
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn complex_literals() {
+        assert_eq!(evaluate("3i", 0.0), Complex::new(0.0, 3.0));
+        assert_eq!(evaluate("2+4i", 0.0), Complex::new(2.0, 4.0));
+        assert_eq!(evaluate("1e-3i", 0.0), Complex::new(0.0, 1e-3));
+        assert_eq!(evaluate("(2+3i)x", 2.0), Complex::new(4.0, 6.0));
+        assert_eq!(evaluate("xi", 2.0), Complex::new(0.0, 2.0));
+        assert_prints_as("3i", "3i", "3i");
+        assert_prints_as("2+4i", "2 + 4i", "2 + 4i");
+        assert_prints_as("1e-3i", "0.001i", "0.001i");
+        assert_prints_as("(2+3i)x", "(2 + 3i)*x", r"\left(2 + 3i\right) \cdot x");
+        assert_prints_as("xi", "x*i", r"x \cdot i");
+
+        // Like `2x^2`, the power takes the unit and not the whole literal
+        assert_eq!(evaluate("2i^2", 0.0), Complex::from(-2.0));
+        assert_prints_as("2i^2", "2*i^2", r"2 \cdot {i}^{2}");
+        assert_eq!(evaluate("(2i)^2", 0.0), Complex::from(-4.0));
+        assert_prints_as("(2i)^2", "(2i)^2", r"{\left(2i\right)}^{2}");
+
+        // `i` is a constant on every evaluation path, never the variable
+        let formula = Formula::new("x + i").unwrap();
+        assert_eq!(formula.evaluate(Complex::from(2.0)).unwrap(), Complex::new(2.0, 1.0));
+        assert_eq!(formula.evaluate_complex(Complex::from(2.0)).unwrap(), Complex::new(2.0, 1.0));
+        assert_eq!(formula.get_vector(2.0, 3.0, 1), vec![Complex::new(2.0, 1.0)]);
+    }
+}
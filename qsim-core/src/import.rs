@@ -0,0 +1,389 @@
+//! Potentials and wavefunctions measured or computed elsewhere, read from data
+//! files and interpolated onto the simulation grid
+
+use crate::complex::Complex;
+use crate::experiment::Grid;
+use crate::linear::{BandedMatrix, Vector};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path};
+
+/// A gap between neighbouring points wider than this many grid spacings, and
+/// this many times their median spacing, is most likely missing data. Meshes
+/// refined around a feature keep their sparse parts, and data coarser than
+/// the grid isn't held against it
+const MAX_GAP: f64 = 10.0;
+/// Slack on the data range relative to its width. Rounding x to f32, as the
+/// GUI does, is allowed for on top of it
+const TOLERANCE: f64 = 1e-6;
+
+/// How values between data points are filled in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Interpolation {
+    Linear,
+    /// Not-a-knot cubic spline, exact for cubics but may overshoot around steps
+    CubicSpline,
+    /// Fritsch-Carlson monotone cubic, which never overshoots the data
+    Monotone,
+}
+impl Default for Interpolation {
+    fn default() -> Self {
+        Interpolation::Linear
+    }
+}
+
+/// Reads `(x, y)` pairs from a `.npy` array with two columns, or from a text
+/// file with two columns separated by commas, semicolons or whitespace
+pub fn read(path: &Path) -> Result<Vec<(f64, f64)>, String> {
+    let result = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("npy") => fs::read(path).map_err(|error| error.to_string()).and_then(|bytes| parse_npy(&bytes)),
+        _ => fs::read_to_string(path).map_err(|error| error.to_string()).and_then(|text| parse_text(&text)),
+    };
+    result.map_err(|error| format!("{}: {}", path.display(), error))
+}
+
+/// Lines starting with `#` or `%` are comments, and a first line that isn't
+/// numbers is taken as a header. Columns past the second are ignored
+pub fn parse_text(text: &str) -> Result<Vec<(f64, f64)>, String> {
+    let mut points = vec![];
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with('%') {
+            continue;
+        }
+        let fields: Vec<&str> =
+            line.split(|c: char| c == ',' || c == ';' || c.is_whitespace()).filter(|field| !field.is_empty()).collect();
+        let values: Result<Vec<f64>, _> = fields.iter().take(2).map(|field| field.parse::<f64>()).collect();
+        match values {
+            Ok(values) if values.len() == 2 => points.push((values[0], values[1])),
+            Err(_) if points.is_empty() => continue,
+            _ => return Err(format!("line {}: expected two numbers, found `{}`", number + 1, line)),
+        }
+    }
+    Ok(points)
+}
+
+/// Arrays of shape `(n, 2)` in either memory order, as float64 or float32
+/// https://numpy.org/doc/stable/reference/generated/numpy.lib.format.html
+pub fn parse_npy(bytes: &[u8]) -> Result<Vec<(f64, f64)>, String> {
+    if bytes.len() < 10 || &bytes[..6] != b"\x93NUMPY" {
+        return Err("Not a .npy file".into());
+    }
+    let (length, start) = match bytes[6] {
+        1 => (u16::from_le_bytes([bytes[8], bytes[9]]) as usize, 10),
+        2 | 3 if bytes.len() >= 12 => (u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]) as usize, 12),
+        version => return Err(format!(".npy version {} is not supported", version)),
+    };
+    let header = bytes
+        .get(start..start + length)
+        .and_then(|header| std::str::from_utf8(header).ok())
+        .ok_or("Truncated .npy header")?;
+    let data = &bytes[start + length..];
+
+    let field = |key: &str| -> Result<&str, String> {
+        let rest = header.split(&format!("'{}':", key)).nth(1).ok_or(format!("Missing `{}` in .npy header", key))?;
+        Ok(rest.trim_start())
+    };
+    let descr = field("descr")?.trim_start_matches('\'').split('\'').next().unwrap_or_default();
+    let fortran_order = field("fortran_order")?.starts_with("True");
+    let shape = field("shape")?;
+    let shape: Vec<usize> = shape
+        .get(1..shape.find(')').ok_or("Invalid .npy shape")?)
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|dimension| !dimension.is_empty())
+        .map(|dimension| dimension.parse().map_err(|_| "Invalid .npy shape"))
+        .collect::<Result<_, _>>()?;
+    let rows = match shape[..] {
+        [rows, 2] => rows,
+        _ => return Err(format!("Expected an array of shape (n, 2), found {:?}", shape)),
+    };
+
+    let values: Vec<f64> = match descr {
+        "<f8" => data.chunks_exact(8).map(|bytes| f64::from_le_bytes(bytes.try_into().unwrap())).collect(),
+        "<f4" => data.chunks_exact(4).map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()) as f64).collect(),
+        _ => return Err(format!("Expected float64 or float32 data, found `{}`", descr)),
+    };
+    if values.len() / 2 < rows {
+        return Err("Truncated .npy data".into());
+    }
+    Ok((0..rows)
+        .map(|row| if fortran_order { (values[row], values[rows + row]) } else { (values[2 * row], values[2 * row + 1]) })
+        .collect())
+}
+
+/// Checks that the points are usable for interpolating onto `grid`: finite,
+/// with strictly increasing x, no large gaps, and covering the grid without
+/// reaching outside it
+pub fn validate(points: &[(f64, f64)], grid: &Grid) -> Result<(), String> {
+    if points.len() < 2 {
+        return Err("At least two points are needed".into());
+    }
+    if let Some(index) = points.iter().position(|(x, y)| !x.is_finite() || !y.is_finite()) {
+        return Err(format!("Point {} is not a finite number", index + 1));
+    }
+    if let Some(index) = points.windows(2).position(|pair| pair[1].0 <= pair[0].0) {
+        return Err(format!(
+            "x must increase, but point {} (x = {}) follows x = {}",
+            index + 2,
+            points[index + 1].0,
+            points[index].0
+        ));
+    }
+
+    let mut spacings: Vec<f64> = points.windows(2).map(|pair| pair[1].0 - pair[0].0).collect();
+    spacings.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let largest = MAX_GAP * spacings[spacings.len() / 2].max(grid.spacing());
+    if let Some(pair) = points.windows(2).find(|pair| pair[1].0 - pair[0].0 > largest) {
+        return Err(format!("Gap in the data between x = {} and x = {}", pair[0].0, pair[1].0));
+    }
+
+    let magnitude = grid.start().abs().max(grid.end().abs());
+    let slack = TOLERANCE * (grid.end() - grid.start()) + f32::EPSILON as f64 * magnitude;
+    let (first, last) = (points[0].0, points[points.len() - 1].0);
+    if first < grid.start() - slack || last > grid.end() + slack {
+        return Err(format!(
+            "The data spans [{}, {}], outside the grid's [{}, {}]",
+            first,
+            last,
+            grid.start(),
+            grid.end()
+        ));
+    }
+    let (start, end) = (grid.position(0), grid.position(grid.resolution() as usize - 1));
+    if first > start + slack || last < end - slack {
+        return Err(format!(
+            "The data spans [{}, {}] but the grid needs values on [{}, {}]",
+            first, last, start, end
+        ));
+    }
+    Ok(())
+}
+
+/// Values at every grid position, after `validate`-ing the points. They are
+/// always finite
+pub fn interpolate(points: &[(f64, f64)], grid: &Grid, interpolation: Interpolation) -> Result<Vec<f64>, String> {
+    validate(points, grid)?;
+    let (xs, ys): (Vec<f64>, Vec<f64>) = points.iter().copied().unzip();
+    let slopes = match interpolation {
+        Interpolation::Linear => None,
+        Interpolation::CubicSpline => Some(spline_slopes(&xs, &ys)?),
+        Interpolation::Monotone => Some(monotone_slopes(&xs, &ys)),
+    };
+
+    let values: Vec<f64> = grid
+        .positions()
+        .map(|x| {
+            // Grid points a rounding error past the data take the value at its end
+            let x = x.max(xs[0]).min(xs[xs.len() - 1]);
+            let i = match xs.partition_point(|value| *value <= x) {
+                0 => 0,
+                index => (index - 1).min(xs.len() - 2),
+            };
+            let (h, t) = (xs[i + 1] - xs[i], (x - xs[i]) / (xs[i + 1] - xs[i]));
+            match &slopes {
+                None => ys[i] + t * (ys[i + 1] - ys[i]),
+                Some(slopes) => hermite(ys[i], ys[i + 1], h * slopes[i], h * slopes[i + 1], t),
+            }
+        })
+        .collect();
+    // Finite points can still overflow, with huge values or nearly equal x
+    if let Some(i) = values.iter().position(|value| !value.is_finite()) {
+        return Err(format!("The interpolated values overflow at x = {}", grid.position(i)));
+    }
+    Ok(values)
+}
+
+/// Cubic Hermite basis on `[0, 1]`, with the tangents already scaled by the interval width
+fn hermite(y0: f64, y1: f64, m0: f64, m1: f64, t: f64) -> f64 {
+    let (t2, t3) = (t * t, t * t * t);
+    (2.0 * t3 - 3.0 * t2 + 1.0) * y0 + (t3 - 2.0 * t2 + t) * m0 + (-2.0 * t3 + 3.0 * t2) * y1 + (t3 - t2) * m1
+}
+
+/// Derivatives of the not-a-knot cubic spline at the knots, whose third
+/// derivative is continuous at the second and second to last knots. Two points
+/// make a line and three a parabola
+fn spline_slopes(xs: &[f64], ys: &[f64]) -> Result<Vec<f64>, String> {
+    let n = xs.len();
+    let h: Vec<f64> = xs.windows(2).map(|pair| pair[1] - pair[0]).collect();
+    let secant: Vec<f64> = (0..n - 1).map(|i| (ys[i + 1] - ys[i]) / h[i]).collect();
+    if n == 2 {
+        return Ok(vec![secant[0]; 2]);
+    }
+    if n == 3 {
+        let middle = (h[1] * secant[0] + h[0] * secant[1]) / (h[0] + h[1]);
+        return Ok(vec![2.0 * secant[0] - middle, middle, 2.0 * secant[1] - middle]);
+    }
+
+    // Continuity of the second derivative at the inner knots. The end rows aren't
+    // diagonally dominant, so this goes through the pivoting banded solver
+    let mut system = BandedMatrix::new(n, 1, 1);
+    let mut rhs = vec![Complex::zero(); n];
+    let mut set = |row: usize, column: usize, value: f64| system.set(row, column, Complex::from(value));
+    let first = h[0] + h[1];
+    set(0, 0, h[1]);
+    set(0, 1, first);
+    rhs[0] = Complex::from(((h[0] + 2.0 * first) * h[1] * secant[0] + h[0] * h[0] * secant[1]) / first);
+    for i in 1..n - 1 {
+        set(i, i - 1, h[i]);
+        set(i, i, 2.0 * (h[i - 1] + h[i]));
+        set(i, i + 1, h[i - 1]);
+        rhs[i] = Complex::from(3.0 * (h[i] * secant[i - 1] + h[i - 1] * secant[i]));
+    }
+    let last = h[n - 3] + h[n - 2];
+    set(n - 1, n - 2, last);
+    set(n - 1, n - 1, h[n - 3]);
+    rhs[n - 1] = Complex::from(
+        (h[n - 2] * h[n - 2] * secant[n - 3] + (2.0 * last + h[n - 2]) * h[n - 3] * secant[n - 2]) / last,
+    );
+
+    Ok(system.solve(&Vector::from(rhs))?.iter().map(|slope| slope.real).collect())
+}
+
+/// Fritsch-Carlson: three point slopes, zeroed at extrema and limited so each
+/// interval stays monotone
+fn monotone_slopes(xs: &[f64], ys: &[f64]) -> Vec<f64> {
+    let n = xs.len();
+    let secant: Vec<f64> = (0..n - 1).map(|i| (ys[i + 1] - ys[i]) / (xs[i + 1] - xs[i])).collect();
+    let mut slopes = vec![0.0; n];
+    slopes[0] = secant[0];
+    slopes[n - 1] = secant[n - 2];
+    for i in 1..n - 1 {
+        if secant[i - 1] * secant[i] > 0.0 {
+            slopes[i] = (secant[i - 1] + secant[i]) / 2.0;
+        }
+    }
+    for i in 0..n - 1 {
+        if secant[i] == 0.0 {
+            slopes[i] = 0.0;
+            slopes[i + 1] = 0.0;
+            continue;
+        }
+        let (a, b) = (slopes[i] / secant[i], slopes[i + 1] / secant[i]);
+        let length = a.hypot(b);
+        if length > 3.0 {
+            slopes[i] = 3.0 * a / length * secant[i];
+            slopes[i + 1] = 3.0 * b / length * secant[i];
+        }
+    }
+    slopes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A version 1 `.npy` file with the header padded to 64 bytes, as numpy writes it
+    fn npy(descr: &str, fortran_order: bool, rows: usize, data: &[u8]) -> Vec<u8> {
+        let order = if fortran_order { "True" } else { "False" };
+        let mut header = format!("{{'descr': '{}', 'fortran_order': {}, 'shape': ({}, 2), }}", descr, order, rows);
+        while (10 + header.len() + 1) % 64 != 0 {
+            header.push(' ');
+        }
+        header.push('\n');
+        let mut bytes = b"\x93NUMPY\x01\x00".to_vec();
+        bytes.extend((header.len() as u16).to_le_bytes());
+        bytes.extend(header.as_bytes());
+        bytes.extend(data);
+        bytes
+    }
+
+    fn grid(resolution: u32) -> Grid {
+        Grid::new(-1.0, 1.0, resolution).unwrap()
+    }
+
+    #[test]
+    fn text_skips_headers_and_comments() {
+        let text = "x, V(x)\n# measured\n% exported\n\n1,2\n3;4\n  5 6 7\n-1e-3\t8.5\n";
+        assert_eq!(parse_text(text).unwrap(), vec![(1.0, 2.0), (3.0, 4.0), (5.0, 6.0), (-1e-3, 8.5)]);
+        assert!(parse_text("1,2\nx,y\n").unwrap_err().starts_with("line 2"));
+        assert!(parse_text("1,2\n3\n").unwrap_err().starts_with("line 2"));
+        assert!(parse_text("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn npy_in_either_order_and_precision() {
+        let float64: Vec<u8> = [0.0f64, 1.0, 2.0, 3.0].iter().flat_map(|value| value.to_le_bytes()).collect();
+        let bytes = npy("<f8", false, 2, &float64);
+        assert_eq!(bytes.iter().position(|byte| *byte == b'\n').map(|end| (end + 1) % 64), Some(0));
+        assert_eq!(parse_npy(&bytes).unwrap(), vec![(0.0, 1.0), (2.0, 3.0)]);
+
+        // Column major stores every x before the y values
+        let float32: Vec<u8> = [0.0f32, 2.0, 1.0, 0.1].iter().flat_map(|value| value.to_le_bytes()).collect();
+        assert_eq!(parse_npy(&npy("<f4", true, 2, &float32)).unwrap(), vec![(0.0, 1.0), (2.0, 0.1f32 as f64)]);
+
+        assert!(parse_npy(b"not numpy at all").is_err());
+        assert!(parse_npy(&npy("<f8", false, 3, &float64)).unwrap_err().contains("Truncated"));
+        assert!(parse_npy(&npy("<i8", false, 2, &float64)).unwrap_err().contains("<i8"));
+        let mut three_columns = npy("<f8", false, 2, &float64);
+        let header = String::from_utf8_lossy(&three_columns).replace("(2, 2)", "(1, 3)");
+        three_columns = header.into_bytes();
+        assert!(parse_npy(&three_columns).is_err());
+    }
+
+    #[test]
+    fn validation() {
+        let grid = grid(20);
+        let line = |xs: &[f64]| xs.iter().map(|x| (*x, 2.0 * x)).collect::<Vec<(f64, f64)>>();
+        assert!(validate(&line(&[-1.0, 1.0]), &grid).is_ok());
+        assert!(validate(&line(&[0.0]), &grid).is_err());
+        assert!(validate(&line(&[-1.0, f64::NAN, 1.0]), &grid).unwrap_err().contains("Point 2"));
+        assert!(validate(&line(&[-1.0, 0.5, 0.0, 1.0]), &grid).unwrap_err().contains("point 3"));
+        assert!(validate(&line(&[-2.0, 1.0]), &grid).unwrap_err().contains("outside"));
+        assert!(validate(&line(&[-1.0, 0.5]), &grid).unwrap_err().contains("needs values"));
+
+        // Evenly spaced data with a run of rows missing
+        let mut missing: Vec<f64> = (0..=20).map(|i| -1.0 + i as f64 * 0.1).collect();
+        missing.drain(5..17);
+        assert!(validate(&line(&missing), &grid).unwrap_err().starts_with("Gap"));
+
+        // A mesh refined around a barrier, with only its ends far apart
+        let mut refined: Vec<f64> = (0..41).map(|i| -0.05 + i as f64 * 0.0025).collect();
+        refined.insert(0, -1.0);
+        refined.push(1.0);
+        assert!(validate(&line(&refined), &grid).is_ok());
+
+        // Data much coarser than the grid
+        assert!(validate(&line(&[-1.0, -0.5, 0.0, 0.5, 1.0]), &self::grid(400)).is_ok());
+    }
+
+    #[test]
+    fn interpolants_reproduce_polynomials() {
+        let grid = grid(50);
+        let cubic = |x: f64| x * x * x - 2.0 * x * x + 0.5 * x + 1.0;
+        let xs = [-1.0, -0.7, -0.2, 0.1, 0.15, 0.6, 1.0];
+        let points: Vec<(f64, f64)> = xs.iter().map(|x| (*x, cubic(*x))).collect();
+        for (x, value) in grid.positions().zip(interpolate(&points, &grid, Interpolation::CubicSpline).unwrap()) {
+            assert!((value - cubic(x)).abs() < 1e-12, "spline at {} = {}, expected {}", x, value, cubic(x));
+        }
+
+        let parabola = |x: f64| 3.0 * x * x - x;
+        let points: Vec<(f64, f64)> = [-1.0, 0.3, 1.0].iter().map(|x| (*x, parabola(*x))).collect();
+        for (x, value) in grid.positions().zip(interpolate(&points, &grid, Interpolation::CubicSpline).unwrap()) {
+            assert!((value - parabola(x)).abs() < 1e-12);
+        }
+
+        let points = [(-1.0, -1.0), (1.0, 3.0)];
+        for interpolation in [Interpolation::Linear, Interpolation::CubicSpline, Interpolation::Monotone] {
+            for (x, value) in grid.positions().zip(interpolate(&points, &grid, interpolation).unwrap()) {
+                assert!((value - (2.0 * x + 1.0)).abs() < 1e-12, "{:?} at {}", interpolation, x);
+            }
+        }
+    }
+
+    #[test]
+    fn monotone_interpolant_does_not_overshoot_a_step() {
+        let grid = grid(200);
+        let points: Vec<(f64, f64)> =
+            (0..=20).map(|i| -1.0 + i as f64 * 0.1).map(|x| (x, if x < 0.0 { 0.0 } else { 1.0 })).collect();
+
+        let monotone = interpolate(&points, &grid, Interpolation::Monotone).unwrap();
+        assert!(monotone.iter().all(|value| (0.0..=1.0).contains(value)));
+        assert!(monotone.windows(2).all(|pair| pair[0] <= pair[1]));
+
+        // Which is what sets it apart from the spline
+        let spline = interpolate(&points, &grid, Interpolation::CubicSpline).unwrap();
+        assert!(spline.iter().any(|value| !(0.0..=1.0).contains(value)));
+    }
+}
@@ -0,0 +1,22 @@
+//! Everything qsim computes, without the GUI: complex arithmetic, formula
+//! parsing, linear algebra and the eigenbasis pipeline that turns a potential
+//! and an initial wavefunction into time frames
+
+pub mod archive;
+pub mod complex;
+pub mod datapoints;
+pub mod experiment;
+pub mod export;
+pub mod formula;
+pub mod import;
+pub mod linear;
+pub mod plot;
+pub mod project;
+pub mod render;
+
+pub use complex::Complex;
+pub use datapoints::{Datapoint, Datapoints};
+pub use experiment::{Experiment, Grid, InitialState, Potential, Solver};
+pub use formula::{Formula, FormulaError};
+pub use linear::Vector;
+pub use project::Project;
@@ -0,0 +1,1687 @@
+use std::{
+    cmp::Ordering,
+    ops::{Add, Index, Mul, Sub},
+};
+use crate::complex::Complex;
+use nalgebra::DVector;
+use rayon::prelude::*;
+
+#[derive(Debug)]
+pub struct Matrix {
+    rows: usize,
+    columns: usize,
+    entries: Vec<Complex>
+}
+impl Matrix {
+    pub fn new(rows: usize, columns: usize) -> Self {
+        Self { rows, columns, entries: vec![Complex::zero(); rows*columns] }
+    }
+
+    pub fn get(&self, row: usize, column: usize) -> Option<Complex> {
+        self.entries.get((self.columns * row) + column).copied()
+    }
+
+    pub fn get_row(&self, row: usize) -> &[Complex] {
+        &self.entries[row * self.columns..(row + 1) * self.columns]
+    }
+
+    /// Todo: Make iterator instead
+    pub fn rows(&self) -> Vec<&[Complex]> {
+        let mut rows = vec![];
+        for row in 0..self.columns {
+            rows.push(&self.entries[row * self.columns..(row + 1) * self.columns])     
+        }
+        rows
+    }
+
+    pub fn set(&mut self, row: usize, column: usize, value: Complex) -> bool {
+        let entry = self.entries.get_mut((self.columns * row) + column);
+        match entry {
+            None => false,
+            Some(entry) => {
+                *entry = value;
+                true
+            }
+        }
+    }
+
+    /// Eigenpairs of a Hermitian matrix, sorted by ascending eigenvalue.
+    /// `iterations` bounds the QL sweeps spent on each eigenvalue
+    pub fn eigenproblem(&self, iterations: u32) -> Result<Vec<(Complex, Vector)>, String> {
+        if self.rows != self.columns {
+            return Err("Matrix is not square".into());
+        }
+        if self.entries.iter().any(|value| !value.real.is_finite() || !value.imag.is_finite()) {
+            return Err("Matrix has non-finite entries".into());
+        }
+        if !self.is_hermitian() {
+            return Err("Matrix is not Hermitian".into());
+        }
+
+        let n = self.rows;
+        let mut a = self.entries.clone();
+        let mut diagonal = vec![0.0; n];
+        let mut subdiagonal = vec![Complex::zero(); n];
+        let mut reflectors = Vec::with_capacity(n.saturating_sub(1));
+
+        // Householder reduction to tridiagonal form: A = Q T Q^H
+        for k in 0..n.saturating_sub(1) {
+            let m = n - k - 1;
+            let mut v: Vec<Complex> = (0..m).map(|j| a[(k + 1 + j) * n + k]).collect();
+            let norm = v.iter().map(|value| value.modulus_squared()).sum::<f64>().sqrt();
+            if norm == 0.0 {
+                reflectors.push(None);
+                continue;
+            }
+
+            let phase = if v[0].is_zero() { Complex::from(1.0) } else { v[0] / v[0].modulus() };
+            let alpha = -phase * norm;
+            v[0] -= alpha;
+            let length = v.iter().map(|value| value.modulus_squared()).sum::<f64>().sqrt();
+            v.iter_mut().for_each(|value| *value /= length);
+
+            // With H = I - 2vv^H, HBH = B - 2vw^H - 2wv^H where p = Bv and w = p - (v^H p)v
+            let p: Vec<Complex> = a[(k + 1) * n..]
+                .par_chunks(n)
+                .map(|row| row[k + 1..].iter().zip(&v).map(|(a, v)| *a * *v).sum())
+                .collect();
+            let projection: f64 = v.iter().zip(&p).map(|(v, p)| (v.conjugate() * *p).real).sum();
+            let w: Vec<Complex> = p.iter().zip(&v).map(|(p, v)| *p - *v * projection).collect();
+            a[(k + 1) * n..].par_chunks_mut(n).enumerate().for_each(|(row, entries)| {
+                for (column, entry) in entries[k + 1..].iter_mut().enumerate() {
+                    *entry -= (v[row] * w[column].conjugate() + w[row] * v[column].conjugate()) * 2.0;
+                }
+            });
+
+            subdiagonal[k] = alpha;
+            reflectors.push(Some(v));
+        }
+        for (i, value) in diagonal.iter_mut().enumerate() {
+            *value = a[i * n + i].real;
+        }
+
+        hermitian_tridiagonal_eigenpairs(diagonal, &subdiagonal, iterations, |u| {
+            // Eigenvectors of A are Q u for eigenvectors u of T
+            for (k, reflector) in reflectors.iter().enumerate().rev() {
+                if let Some(v) = reflector {
+                    let tail = &mut u[k + 1..];
+                    let overlap: Complex = v.iter().zip(tail.iter()).map(|(v, u)| v.conjugate() * *u).sum();
+                    for (u, v) in tail.iter_mut().zip(v) {
+                        *u -= *v * overlap * 2.0;
+                    }
+                }
+            }
+        })
+    }
+
+    pub fn identity(size: usize) -> Self {
+        let mut identity = Self::new(size, size);
+        for i in 0..size {
+            identity.entries[i * size + i] = Complex::from(1.0);
+        }
+        identity
+    }
+
+    fn product(&self, other: &Matrix) -> Matrix {
+        let mut product = Matrix::new(self.rows, other.columns);
+        for row in 0..self.rows {
+            for k in 0..self.columns {
+                let factor = self.entries[row * self.columns + k];
+                if factor.is_zero() {
+                    continue;
+                }
+                for column in 0..other.columns {
+                    product.entries[row * other.columns + column] += factor * other.entries[k * other.columns + column];
+                }
+            }
+        }
+        product
+    }
+
+    /// Sum of equally sized matrices scaled by real factors
+    fn combination(terms: &[(f64, &Matrix)]) -> Matrix {
+        let (_, first) = terms[0];
+        let mut sum = Matrix::new(first.rows, first.columns);
+        for (factor, matrix) in terms {
+            for (entry, value) in sum.entries.iter_mut().zip(&matrix.entries) {
+                *entry += *value * *factor;
+            }
+        }
+        sum
+    }
+
+    /// Solves `self X = rhs` for every column of `rhs` by LU with partial pivoting
+    fn solve_many(mut self, rhs: &Matrix) -> Result<Matrix, String> {
+        let n = self.rows;
+        let mut solution = Matrix { rows: rhs.rows, columns: rhs.columns, entries: rhs.entries.clone() };
+        let width = solution.columns;
+        for k in 0..n {
+            let pivot_row = (k..n)
+                .max_by(|&a, &b| {
+                    self.entries[a * n + k]
+                        .modulus()
+                        .partial_cmp(&self.entries[b * n + k].modulus())
+                        .unwrap_or(Ordering::Equal)
+                })
+                .unwrap();
+            let pivot = self.entries[pivot_row * n + k];
+            if pivot.is_zero() {
+                return Err("Matrix is singular".into());
+            }
+            if pivot_row != k {
+                for column in 0..n {
+                    self.entries.swap(k * n + column, pivot_row * n + column);
+                }
+                for column in 0..width {
+                    solution.entries.swap(k * width + column, pivot_row * width + column);
+                }
+            }
+            for row in k + 1..n {
+                let factor = self.entries[row * n + k] / pivot;
+                if factor.is_zero() {
+                    continue;
+                }
+                for column in k..n {
+                    let value = self.entries[k * n + column];
+                    self.entries[row * n + column] -= factor * value;
+                }
+                for column in 0..width {
+                    let value = solution.entries[k * width + column];
+                    solution.entries[row * width + column] -= factor * value;
+                }
+            }
+        }
+        for k in (0..n).rev() {
+            for column in 0..width {
+                let mut value = solution.entries[k * width + column];
+                for j in k + 1..n {
+                    value -= self.entries[k * n + j] * solution.entries[j * width + column];
+                }
+                solution.entries[k * width + column] = value / self.entries[k * n + k];
+            }
+        }
+        Ok(solution)
+    }
+
+    fn is_hermitian(&self) -> bool {
+        let scale = self.entries.iter().map(|value| value.modulus()).fold(0.0, f64::max);
+        let tolerance = 1e-12 * scale.max(1.0);
+        (0..self.rows).all(|row| {
+            (row..self.columns).all(|column| {
+                let upper = self.entries[row * self.columns + column];
+                let lower = self.entries[column * self.columns + row];
+                (upper - lower.conjugate()).modulus() <= tolerance
+            })
+        })
+    }
+}
+
+/// Eigenpairs of the Hermitian tridiagonal matrix with the given real diagonal
+/// and complex subdiagonal, sorted by ascending eigenvalue. `transform` maps each
+/// eigenvector back to the basis of the original matrix
+fn hermitian_tridiagonal_eigenpairs(
+    mut diagonal: Vec<f64>,
+    subdiagonal: &[Complex],
+    iterations: u32,
+    transform: impl Fn(&mut [Complex]) + Sync,
+) -> Result<Vec<(Complex, Vector)>, String> {
+    let n = diagonal.len();
+
+    // A diagonal unitary D makes T real: T = D T' D^H
+    let mut phases = vec![Complex::from(1.0); n];
+    let mut offdiagonal = vec![0.0; n];
+    for j in 0..n.saturating_sub(1) {
+        let modulus = subdiagonal[j].modulus();
+        offdiagonal[j] = modulus;
+        phases[j + 1] = if modulus == 0.0 {
+            phases[j]
+        } else {
+            phases[j] * subdiagonal[j] / modulus
+        };
+    }
+
+    let vectors = tridiagonal_ql(&mut diagonal, &mut offdiagonal, iterations)?;
+    // Finite entries can still overflow on the way
+    if !diagonal.iter().all(|value| value.is_finite()) {
+        return Err("Eigenvalues are not finite".into());
+    }
+
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by(|&i, &j| diagonal[i].partial_cmp(&diagonal[j]).unwrap_or(Ordering::Equal));
+    Ok(order
+        .into_par_iter()
+        .map(|j| {
+            let mut u: Vec<Complex> = vectors[j * n..(j + 1) * n]
+                .iter()
+                .zip(&phases)
+                .map(|(y, phase)| *phase * *y)
+                .collect();
+            transform(&mut u);
+            let mut vector = Vector(u);
+            vector.normalize();
+            (Complex::from(diagonal[j]), vector)
+        })
+        .collect())
+}
+
+/// Implicit QL with Wilkinson shifts on a real symmetric tridiagonal matrix,
+/// where `offdiagonal[i]` couples rows `i` and `i + 1`. Leaves the eigenvalues
+/// in `diagonal` and returns the eigenvectors as consecutive rows
+fn tridiagonal_ql(diagonal: &mut [f64], offdiagonal: &mut [f64], iterations: u32) -> Result<Vec<f64>, String> {
+    let n = diagonal.len();
+    let mut vectors = vec![0.0; n * n];
+    for i in 0..n {
+        vectors[i * n + i] = 1.0;
+    }
+    if n == 0 {
+        return Ok(vectors);
+    }
+    offdiagonal[n - 1] = 0.0;
+
+    let mut shift = 0.0;
+    let mut largest: f64 = 0.0;
+    for l in 0..n {
+        largest = largest.max(diagonal[l].abs() + offdiagonal[l].abs());
+        let mut m = l;
+        while offdiagonal[m].abs() > f64::EPSILON * largest {
+            m += 1;
+        }
+
+        if m > l {
+            let mut iteration = 0;
+            loop {
+                iteration += 1;
+                if iteration > iterations {
+                    return Err("Eigenvalues did not converge".into());
+                }
+
+                let g = diagonal[l];
+                let mut p = (diagonal[l + 1] - g) / (2.0 * offdiagonal[l]);
+                let mut r = p.hypot(1.0);
+                if p < 0.0 {
+                    r = -r;
+                }
+                diagonal[l] = offdiagonal[l] / (p + r);
+                diagonal[l + 1] = offdiagonal[l] * (p + r);
+                let next = diagonal[l + 1];
+                let h = g - diagonal[l];
+                for value in &mut diagonal[l + 2..] {
+                    *value -= h;
+                }
+                shift += h;
+
+                p = diagonal[m];
+                let (mut c, mut c2, mut c3) = (1.0, 1.0, 1.0);
+                let (mut s, mut s2) = (0.0, 0.0);
+                let coupling = offdiagonal[l + 1];
+                for i in (l..m).rev() {
+                    c3 = c2;
+                    c2 = c;
+                    s2 = s;
+                    let g = c * offdiagonal[i];
+                    let h = c * p;
+                    r = p.hypot(offdiagonal[i]);
+                    offdiagonal[i + 1] = s * r;
+                    s = offdiagonal[i] / r;
+                    c = p / r;
+                    p = c * diagonal[i] - s * g;
+                    diagonal[i + 1] = h + s * (c * g + s * diagonal[i]);
+
+                    let (left, right) = vectors.split_at_mut((i + 1) * n);
+                    let (current, following) = (&mut left[i * n..], &mut right[..n]);
+                    for (a, b) in current.iter_mut().zip(following.iter_mut()) {
+                        let h = *b;
+                        *b = s * *a + c * h;
+                        *a = c * *a - s * h;
+                    }
+                }
+                p = -s * s2 * c3 * coupling * offdiagonal[l] / next;
+                offdiagonal[l] = s * p;
+                diagonal[l] = c * p;
+
+                if offdiagonal[l].abs() <= f64::EPSILON * largest {
+                    break;
+                }
+            }
+        }
+        diagonal[l] += shift;
+        offdiagonal[l] = 0.0;
+    }
+
+    Ok(vectors)
+}
+/// Anything that acts linearly on a vector, whatever its storage
+pub trait LinearOperator {
+    /// Number of rows and columns
+    fn shape(&self) -> (usize, usize);
+
+    fn apply(&self, vector: &Vector) -> Result<Vector, String>;
+}
+impl LinearOperator for Matrix {
+    fn shape(&self) -> (usize, usize) {
+        (self.rows, self.columns)
+    }
+
+    fn apply(&self, vector: &Vector) -> Result<Vector, String> {
+        if self.columns != vector.len() {
+            return Err("Mismatched dimensions".into());
+        }
+
+        Ok(Vector(
+            self.entries
+                .par_chunks(self.columns.max(1))
+                .map(|row| row.iter().zip(vector.iter()).map(|(a, b)| *a * *b).sum())
+                .collect(),
+        ))
+    }
+}
+
+/// Square matrix that is zero outside the main diagonal and its two neighbours
+#[derive(Debug, Clone)]
+pub struct TridiagonalMatrix {
+    /// Entries `(i + 1, i)`
+    lower: Vec<Complex>,
+    diagonal: Vec<Complex>,
+    /// Entries `(i, i + 1)`
+    upper: Vec<Complex>,
+}
+impl TridiagonalMatrix {
+    pub fn new(size: usize) -> Self {
+        let off = size.saturating_sub(1);
+        Self {
+            lower: vec![Complex::zero(); off],
+            diagonal: vec![Complex::zero(); size],
+            upper: vec![Complex::zero(); off],
+        }
+    }
+
+    pub fn from_diagonals(lower: Vec<Complex>, diagonal: Vec<Complex>, upper: Vec<Complex>) -> Result<Self, String> {
+        let off = diagonal.len().saturating_sub(1);
+        if lower.len() != off || upper.len() != off {
+            return Err("Mismatched dimensions".into());
+        }
+        Ok(Self { lower, diagonal, upper })
+    }
+
+    pub fn size(&self) -> usize {
+        self.diagonal.len()
+    }
+
+    pub fn get(&self, row: usize, column: usize) -> Option<Complex> {
+        if row >= self.size() || column >= self.size() {
+            return None;
+        }
+        Some(if row == column {
+            self.diagonal[row]
+        } else if row == column + 1 {
+            self.lower[column]
+        } else if column == row + 1 {
+            self.upper[row]
+        } else {
+            Complex::zero()
+        })
+    }
+
+    /// Returns false when the entry lies outside the matrix or its band
+    pub fn set(&mut self, row: usize, column: usize, value: Complex) -> bool {
+        let entry = if row == column {
+            self.diagonal.get_mut(row)
+        } else if row == column + 1 {
+            self.lower.get_mut(column)
+        } else if column == row + 1 {
+            self.upper.get_mut(row)
+        } else {
+            None
+        };
+        match entry {
+            None => false,
+            Some(entry) => {
+                *entry = value;
+                true
+            }
+        }
+    }
+
+    /// Thomas algorithm, which does not pivot and so expects a diagonally
+    /// dominant or otherwise well-conditioned matrix
+    pub fn solve(&self, rhs: &Vector) -> Result<Vector, String> {
+        let n = self.size();
+        if rhs.len() != n {
+            return Err("Mismatched dimensions".into());
+        }
+
+        let mut upper = vec![Complex::zero(); n];
+        let mut solution = rhs.0.clone();
+        for i in 0..n {
+            let mut pivot = self.diagonal[i];
+            if i > 0 {
+                pivot -= self.lower[i - 1] * upper[i - 1];
+                let previous = solution[i - 1];
+                solution[i] -= self.lower[i - 1] * previous;
+            }
+            if pivot.is_zero() {
+                return Err("Matrix is singular".into());
+            }
+            if i + 1 < n {
+                upper[i] = self.upper[i] / pivot;
+            }
+            solution[i] /= pivot;
+        }
+        for i in (0..n.saturating_sub(1)).rev() {
+            let next = solution[i + 1];
+            solution[i] -= upper[i] * next;
+        }
+
+        Ok(Vector(solution))
+    }
+
+    /// Eigenpairs of a Hermitian tridiagonal matrix, sorted by ascending eigenvalue
+    pub fn eigenproblem(&self, iterations: u32) -> Result<Vec<(Complex, Vector)>, String> {
+        let finite = |value: &Complex| value.real.is_finite() && value.imag.is_finite();
+        if !self.diagonal.iter().chain(&self.lower).chain(&self.upper).all(finite) {
+            return Err("Matrix has non-finite entries".into());
+        }
+        let scale = self
+            .diagonal
+            .iter()
+            .chain(&self.lower)
+            .chain(&self.upper)
+            .map(|value| value.modulus())
+            .fold(0.0, f64::max);
+        let tolerance = 1e-12 * scale.max(1.0);
+        let hermitian = self.diagonal.iter().all(|value| value.imag.abs() <= tolerance)
+            && self
+                .lower
+                .iter()
+                .zip(&self.upper)
+                .all(|(lower, upper)| (*lower - upper.conjugate()).modulus() <= tolerance);
+        if !hermitian {
+            return Err("Matrix is not Hermitian".into());
+        }
+
+        let diagonal = self.diagonal.iter().map(|value| value.real).collect();
+        hermitian_tridiagonal_eigenpairs(diagonal, &self.lower, iterations, |_| {})
+    }
+}
+impl LinearOperator for TridiagonalMatrix {
+    fn shape(&self) -> (usize, usize) {
+        (self.size(), self.size())
+    }
+
+    fn apply(&self, vector: &Vector) -> Result<Vector, String> {
+        let n = self.size();
+        if vector.len() != n {
+            return Err("Mismatched dimensions".into());
+        }
+
+        Ok(Vector(
+            (0..n)
+                .map(|i| {
+                    let mut value = self.diagonal[i] * vector[i];
+                    if i > 0 {
+                        value += self.lower[i - 1] * vector[i - 1];
+                    }
+                    if i + 1 < n {
+                        value += self.upper[i] * vector[i + 1];
+                    }
+                    value
+                })
+                .collect(),
+        ))
+    }
+}
+
+/// Square matrix that is zero more than `lower` entries below or `upper`
+/// entries above the diagonal
+#[derive(Debug, Clone)]
+pub struct BandedMatrix {
+    size: usize,
+    lower: usize,
+    upper: usize,
+    /// Row-major band, with `(row, column)` at `row * width + column + lower - row`
+    entries: Vec<Complex>,
+}
+impl BandedMatrix {
+    pub fn new(size: usize, lower: usize, upper: usize) -> Self {
+        Self { size, lower, upper, entries: vec![Complex::zero(); size * (lower + upper + 1)] }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    fn width(&self) -> usize {
+        self.lower + self.upper + 1
+    }
+
+    fn index(&self, row: usize, column: usize) -> Option<usize> {
+        if row >= self.size || column >= self.size || column + self.lower < row || row + self.upper < column {
+            return None;
+        }
+        Some(row * self.width() + column + self.lower - row)
+    }
+
+    pub fn get(&self, row: usize, column: usize) -> Option<Complex> {
+        if row >= self.size || column >= self.size {
+            return None;
+        }
+        Some(self.index(row, column).map_or(Complex::zero(), |index| self.entries[index]))
+    }
+
+    /// Returns false when the entry lies outside the matrix or its band
+    pub fn set(&mut self, row: usize, column: usize, value: Complex) -> bool {
+        match self.index(row, column) {
+            None => false,
+            Some(index) => {
+                self.entries[index] = value;
+                true
+            }
+        }
+    }
+
+    /// LU decomposition with partial pivoting. Row swaps widen the upper band
+    /// by `lower`, so the factorization works on a widened copy
+    pub fn solve(&self, rhs: &Vector) -> Result<Vector, String> {
+        let n = self.size;
+        if rhs.len() != n {
+            return Err("Mismatched dimensions".into());
+        }
+
+        let (lower, upper) = (self.lower, self.upper + self.lower);
+        let width = lower + upper + 1;
+        let at = |row: usize, column: usize| row * width + column + lower - row;
+        let mut band = vec![Complex::zero(); n * width];
+        for row in 0..n {
+            for column in row.saturating_sub(self.lower)..n.min(row + self.upper + 1) {
+                band[at(row, column)] = self.entries[self.index(row, column).unwrap()];
+            }
+        }
+        let mut solution = rhs.0.clone();
+
+        for k in 0..n {
+            let last_row = n.min(k + lower + 1);
+            let last_column = n.min(k + upper + 1);
+            let pivot_row = (k..last_row)
+                .max_by(|&a, &b| {
+                    band[at(a, k)]
+                        .modulus()
+                        .partial_cmp(&band[at(b, k)].modulus())
+                        .unwrap_or(Ordering::Equal)
+                })
+                .unwrap();
+            let pivot = band[at(pivot_row, k)];
+            if pivot.is_zero() {
+                return Err("Matrix is singular".into());
+            }
+            if pivot_row != k {
+                for column in k..last_column {
+                    band.swap(at(k, column), at(pivot_row, column));
+                }
+                solution.swap(k, pivot_row);
+            }
+
+            for row in k + 1..last_row {
+                let factor = band[at(row, k)] / pivot;
+                if factor.is_zero() {
+                    continue;
+                }
+                band[at(row, k)] = Complex::zero();
+                for column in k + 1..last_column {
+                    let value = band[at(k, column)];
+                    band[at(row, column)] -= factor * value;
+                }
+                let value = solution[k];
+                solution[row] -= factor * value;
+            }
+        }
+
+        for k in (0..n).rev() {
+            let mut value = solution[k];
+            for column in k + 1..n.min(k + upper + 1) {
+                value -= band[at(k, column)] * solution[column];
+            }
+            solution[k] = value / band[at(k, k)];
+        }
+
+        Ok(Vector(solution))
+    }
+}
+impl LinearOperator for BandedMatrix {
+    fn shape(&self) -> (usize, usize) {
+        (self.size, self.size)
+    }
+
+    fn apply(&self, vector: &Vector) -> Result<Vector, String> {
+        let n = self.size;
+        if vector.len() != n {
+            return Err("Mismatched dimensions".into());
+        }
+
+        Ok(Vector(
+            (0..n)
+                .map(|row| {
+                    (row.saturating_sub(self.lower)..n.min(row + self.upper + 1))
+                        .map(|column| self.entries[self.index(row, column).unwrap()] * vector[column])
+                        .sum()
+                })
+                .collect(),
+        ))
+    }
+}
+impl From<&TridiagonalMatrix> for BandedMatrix {
+    fn from(matrix: &TridiagonalMatrix) -> Self {
+        let mut banded = BandedMatrix::new(matrix.size(), 1, 1);
+        for i in 0..matrix.size() {
+            for j in i.saturating_sub(1)..matrix.size().min(i + 2) {
+                banded.set(i, j, matrix.get(i, j).unwrap());
+            }
+        }
+        banded
+    }
+}
+
+/// Compressed sparse row matrix
+#[derive(Debug, Clone)]
+pub struct SparseMatrix {
+    rows: usize,
+    columns: usize,
+    /// Row `i` owns `values[row_offsets[i]..row_offsets[i + 1]]`
+    row_offsets: Vec<usize>,
+    column_indices: Vec<usize>,
+    values: Vec<Complex>,
+}
+impl SparseMatrix {
+    /// Builds the matrix from `(row, column, value)` entries, adding up repeated positions
+    pub fn from_triplets(rows: usize, columns: usize, triplets: &[(usize, usize, Complex)]) -> Result<Self, String> {
+        if triplets.iter().any(|&(row, column, _)| row >= rows || column >= columns) {
+            return Err("Entry out of bounds".into());
+        }
+
+        let mut sorted = triplets.to_vec();
+        sorted.sort_by_key(|&(row, column, _)| (row, column));
+
+        let mut row_offsets = vec![0; rows + 1];
+        let mut column_indices: Vec<usize> = vec![];
+        let mut values: Vec<Complex> = vec![];
+        let mut last = None;
+        for (row, column, value) in sorted {
+            if last == Some((row, column)) {
+                *values.last_mut().unwrap() += value;
+                continue;
+            }
+            last = Some((row, column));
+            row_offsets[row + 1] += 1;
+            column_indices.push(column);
+            values.push(value);
+        }
+        for row in 0..rows {
+            row_offsets[row + 1] += row_offsets[row];
+        }
+
+        Ok(Self { rows, columns, row_offsets, column_indices, values })
+    }
+
+    /// Number of stored entries
+    pub fn nonzeros(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn get(&self, row: usize, column: usize) -> Option<Complex> {
+        if row >= self.rows || column >= self.columns {
+            return None;
+        }
+        let range = self.row_offsets[row]..self.row_offsets[row + 1];
+        Some(match self.column_indices[range.clone()].binary_search(&column) {
+            Ok(offset) => self.values[range.start + offset],
+            Err(_) => Complex::zero(),
+        })
+    }
+}
+impl LinearOperator for SparseMatrix {
+    fn shape(&self) -> (usize, usize) {
+        (self.rows, self.columns)
+    }
+
+    fn apply(&self, vector: &Vector) -> Result<Vector, String> {
+        if vector.len() != self.columns {
+            return Err("Mismatched dimensions".into());
+        }
+
+        Ok(Vector(
+            self.row_offsets
+                .windows(2)
+                .map(|range| {
+                    (range[0]..range[1])
+                        .map(|entry| self.values[entry] * vector[self.column_indices[entry]])
+                        .sum()
+                })
+                .collect(),
+        ))
+    }
+}
+impl From<&TridiagonalMatrix> for SparseMatrix {
+    fn from(matrix: &TridiagonalMatrix) -> Self {
+        let n = matrix.size();
+        let triplets: Vec<(usize, usize, Complex)> = (0..n)
+            .flat_map(|i| (i.saturating_sub(1)..n.min(i + 2)).map(move |j| (i, j)))
+            .map(|(i, j)| (i, j, matrix.get(i, j).unwrap()))
+            .collect();
+        Self::from_triplets(n, n, &triplets).unwrap()
+    }
+}
+
+/// Padé degrees tried by `expm`, each with the largest 1-norm it is accurate for
+/// in double precision (Higham, 2005)
+const PADE_DEGREES: [(usize, f64); 5] = [
+    (3, 1.495585217958292e-2),
+    (5, 2.53939833006323e-1),
+    (7, 9.504178996162932e-1),
+    (9, 2.097847961257068),
+    (13, 5.371920351148152),
+];
+
+/// Matrix exponential by scaling and squaring with a Padé approximant.
+/// Work is cubic in the size, so this is meant for small dense matrices
+pub fn expm(matrix: &Matrix) -> Result<Matrix, String> {
+    if matrix.rows != matrix.columns {
+        return Err("Matrix is not square".into());
+    }
+    if matrix.entries.iter().any(|value| !value.real.is_finite() || !value.imag.is_finite()) {
+        return Err("Matrix has non-finite entries".into());
+    }
+
+    let n = matrix.rows;
+    let norm = (0..n)
+        .map(|column| (0..n).map(|row| matrix.entries[row * n + column].modulus()).sum::<f64>())
+        .fold(0.0, f64::max);
+
+    let identity = Matrix::identity(n);
+    let square = matrix.product(matrix);
+    let (u, v, squarings) = if let Some(&(degree, _)) = PADE_DEGREES[..4].iter().find(|(_, theta)| norm <= *theta) {
+        let b = pade_coefficients(degree);
+        let mut powers = vec![identity, square];
+        while powers.len() <= degree / 2 {
+            let next = powers.last().unwrap().product(&powers[1]);
+            powers.push(next);
+        }
+        let odd = Matrix::combination(&(0..=degree / 2).map(|k| (b[2 * k + 1], &powers[k])).collect::<Vec<_>>());
+        let even = Matrix::combination(&(0..=degree / 2).map(|k| (b[2 * k], &powers[k])).collect::<Vec<_>>());
+        (matrix.product(&odd), even, 0)
+    } else {
+        let squarings = (norm / PADE_DEGREES[4].1).log2().ceil().max(0.0) as i32;
+        let scale = 0.5f64.powi(squarings);
+        let a = Matrix::combination(&[(scale, matrix)]);
+        let a2 = Matrix::combination(&[(scale * scale, &square)]);
+        let a4 = a2.product(&a2);
+        let a6 = a4.product(&a2);
+        let b = pade_coefficients(13);
+        let odd_high = Matrix::combination(&[(b[13], &a6), (b[11], &a4), (b[9], &a2)]);
+        let odd_low = Matrix::combination(&[(b[7], &a6), (b[5], &a4), (b[3], &a2), (b[1], &identity)]);
+        let odd = Matrix::combination(&[(1.0, &a6.product(&odd_high)), (1.0, &odd_low)]);
+        let even_high = Matrix::combination(&[(b[12], &a6), (b[10], &a4), (b[8], &a2)]);
+        let even_low = Matrix::combination(&[(b[6], &a6), (b[4], &a4), (b[2], &a2), (b[0], &identity)]);
+        let even = Matrix::combination(&[(1.0, &a6.product(&even_high)), (1.0, &even_low)]);
+        (a.product(&odd), even, squarings)
+    };
+
+    // r = (V - U)^-1 (V + U), then undo the scaling by repeated squaring
+    let mut result = Matrix::combination(&[(1.0, &v), (-1.0, &u)])
+        .solve_many(&Matrix::combination(&[(1.0, &v), (1.0, &u)]))?;
+    for _ in 0..squarings {
+        result = result.product(&result);
+    }
+    Ok(result)
+}
+
+fn pade_coefficients(degree: usize) -> &'static [f64] {
+    match degree {
+        3 => &[120.0, 60.0, 12.0, 1.0],
+        5 => &[30240.0, 15120.0, 3360.0, 420.0, 30.0, 1.0],
+        7 => &[17297280.0, 8648640.0, 1995840.0, 277200.0, 25200.0, 1512.0, 56.0, 1.0],
+        9 => &[
+            17643225600.0, 8821612800.0, 2075673600.0, 302702400.0, 30270240.0, 2162160.0, 110880.0, 3960.0,
+            90.0, 1.0,
+        ],
+        _ => &[
+            64764752532480000.0, 32382376266240000.0, 7771770303897600.0, 1187353796428800.0,
+            129060195264000.0, 10559470521600.0, 670442572800.0, 33522128640.0, 1323241920.0, 40840800.0,
+            960960.0, 16380.0, 182.0, 1.0,
+        ],
+    }
+}
+
+/// Largest Krylov subspace built per step of `krylov_propagate`
+const KRYLOV_DIMENSION: usize = 30;
+
+/// Steps `krylov_propagate` takes before giving up on reaching the tolerance
+const KRYLOV_STEPS: usize = 10_000;
+
+/// Computes `exp(-iHt) state` for a Hermitian `hamiltonian` using Lanczos
+/// iterations, so only products with H are needed. The step is shortened
+/// until the a posteriori error estimate of each step stays within its share
+/// of `tolerance`, which must be at least the machine epsilon. Needing more than
+/// `KRYLOV_STEPS` steps is an error
+pub fn krylov_propagate(
+    hamiltonian: &impl LinearOperator,
+    state: &Vector,
+    time: f64,
+    tolerance: f64,
+) -> Result<Vector, String> {
+    let n = state.len();
+    if hamiltonian.shape() != (n, n) {
+        return Err("Mismatched dimensions".into());
+    }
+    if !time.is_finite() || tolerance.is_nan() || tolerance < f64::EPSILON {
+        return Err("Time must be finite and tolerance at least the machine epsilon".into());
+    }
+
+    let mut current = state.0.clone();
+    let mut elapsed: f64 = 0.0;
+    let mut step = time;
+    let mut steps = 0;
+    while elapsed.abs() < time.abs() {
+        // A tolerance near the rounding error could otherwise creep forward by ε t
+        steps += 1;
+        if steps > KRYLOV_STEPS {
+            return Err(format!("Reaching a tolerance of {} takes more than {} steps", tolerance, KRYLOV_STEPS));
+        }
+        let norm = current.iter().map(|value| value.modulus_squared()).sum::<f64>().sqrt();
+        if norm == 0.0 {
+            break;
+        }
+
+        // Lanczos with full reorthogonalization, which is cheap at this size.
+        // The basis depends only on the current state, not on the step length
+        let mut basis = vec![current.iter().map(|value| *value / norm).collect::<Vec<Complex>>()];
+        let mut alphas = vec![];
+        let mut betas = vec![];
+        let mut residual = 0.0;
+        for j in 0..KRYLOV_DIMENSION.min(n) {
+            let mut w = hamiltonian.apply(&Vector(basis[j].clone()))?.0;
+            alphas.push(basis[j].iter().zip(&w).map(|(v, w)| (v.conjugate() * *w).real).sum::<f64>());
+            for previous in &basis {
+                let overlap: Complex = previous.iter().zip(&w).map(|(p, w)| p.conjugate() * *w).sum();
+                for (w, p) in w.iter_mut().zip(previous) {
+                    *w -= overlap * *p;
+                }
+            }
+            let beta = w.iter().map(|value| value.modulus_squared()).sum::<f64>().sqrt();
+            if beta <= 1e-12 * alphas[j].abs().max(1.0) {
+                // The subspace is invariant, so the projection is exact
+                residual = 0.0;
+                break;
+            }
+            residual = beta;
+            if j + 1 < KRYLOV_DIMENSION.min(n) {
+                betas.push(Complex::from(beta));
+                basis.push(w.iter().map(|value| *value / beta).collect());
+            }
+        }
+        let size = alphas.len();
+        betas.truncate(size.saturating_sub(1));
+        let eigenpairs = hermitian_tridiagonal_eigenpairs(alphas, &betas, 30, |_| {})?;
+
+        // exp(-iTt) e1 in the Lanczos basis, shrinking the step until it is accurate
+        let remaining = time - elapsed;
+        step = step.abs().min(remaining.abs()).copysign(time);
+        let coefficients = loop {
+            let mut coefficients = vec![Complex::zero(); size];
+            for (value, vector) in &eigenpairs {
+                let weight = vector[0].conjugate() * (Complex::new(0.0, -step) * *value).exp();
+                for (coefficient, component) in coefficients.iter_mut().zip(vector.iter()) {
+                    *coefficient += *component * weight;
+                }
+            }
+            let error = residual * norm * coefficients[size - 1].modulus();
+            if error <= tolerance * step.abs() / time.abs() || step.abs() <= f64::EPSILON * time.abs() {
+                break coefficients;
+            }
+            step /= 2.0;
+        };
+
+        current = vec![Complex::zero(); n];
+        for (vector, coefficient) in basis.iter().zip(&coefficients) {
+            for (value, component) in current.iter_mut().zip(vector) {
+                *value += *component * *coefficient * norm;
+            }
+        }
+        elapsed = if step == remaining { time } else { elapsed + step };
+        step *= 2.0;
+    }
+
+    Ok(Vector(current))
+}
+
+/// Observable on the simulation grid, composed lazily from the built-in operators
+/// and applied right to left
+#[derive(Debug, Clone)]
+pub enum Observable {
+    Matrix(SparseMatrix),
+    Scaled(Complex, Box<Observable>),
+    Sum(Box<Observable>, Box<Observable>),
+    Product(Box<Observable>, Box<Observable>),
+}
+impl Observable {
+    /// X, for a grid of `size` points starting at `start`
+    pub fn position(size: usize, start: f64, spacing: f64) -> Self {
+        Observable::Matrix(diagonal((0..size).map(|i| Complex::from(start + i as f64 * spacing))))
+    }
+
+    /// V, multiplication by the sampled potential
+    pub fn potential(potential: &Vector) -> Self {
+        Observable::Matrix(diagonal(potential.iter().copied()))
+    }
+
+    /// P = -i d/dx by central differences, with the state vanishing outside the grid
+    pub fn momentum(size: usize, spacing: f64) -> Self {
+        let coupling = Complex::new(0.0, -0.5 / spacing);
+        Observable::Matrix(stencil(size, -coupling, Complex::zero(), coupling))
+    }
+
+    /// P² = -d²/dx² by the three-point stencil
+    pub fn momentum_squared(size: usize, spacing: f64) -> Self {
+        let coupling = Complex::from(-1.0 / (spacing * spacing));
+        Observable::Matrix(stencil(size, coupling, coupling * -2.0, coupling))
+    }
+
+    /// H = P² + V. Unit spacing gives the Hamiltonian that `simulate` diagonalizes
+    pub fn hamiltonian(potential: &Vector, spacing: f64) -> Self {
+        Observable::momentum_squared(potential.len(), spacing) + Observable::potential(potential)
+    }
+
+    pub fn scaled_by(self, scalar: Complex) -> Self {
+        Observable::Scaled(scalar, Box::new(self))
+    }
+
+    /// [A, B] = AB - BA
+    pub fn commutator(&self, other: &Observable) -> Self {
+        self.clone() * other.clone() - other.clone() * self.clone()
+    }
+
+    /// ⟨A⟩ in `state`, which need not be normalized
+    pub fn expectation(&self, state: &Vector) -> Result<Complex, String> {
+        let norm = state.inner_product(state)?.real;
+        if norm == 0.0 {
+            return Err("State is zero".into());
+        }
+        Ok(state.inner_product(&self.apply(state)?)? / norm)
+    }
+
+    /// ΔA = ‖(A - ⟨A⟩)ψ‖ for normalized ψ
+    pub fn uncertainty(&self, state: &Vector) -> Result<f64, String> {
+        let mean = self.expectation(state)?;
+        let mut residual = self.apply(state)?;
+        residual.axpy(-mean, state);
+        Ok((residual.inner_product(&residual)?.real / state.inner_product(state)?.real).sqrt())
+    }
+
+    /// Robertson's lower bound on ΔA ΔB, |⟨[A, B]⟩| / 2
+    pub fn uncertainty_bound(&self, other: &Observable, state: &Vector) -> Result<f64, String> {
+        Ok(self.commutator(other).expectation(state)?.modulus() / 2.0)
+    }
+}
+impl LinearOperator for Observable {
+    fn shape(&self) -> (usize, usize) {
+        match self {
+            Observable::Matrix(matrix) => matrix.shape(),
+            Observable::Scaled(_, operator) => operator.shape(),
+            Observable::Sum(left, _) => left.shape(),
+            Observable::Product(left, right) => (left.shape().0, right.shape().1),
+        }
+    }
+
+    fn apply(&self, vector: &Vector) -> Result<Vector, String> {
+        match self {
+            Observable::Matrix(matrix) => matrix.apply(vector),
+            Observable::Scaled(scalar, operator) => Ok(operator.apply(vector)?.scaled_by(*scalar)),
+            Observable::Sum(left, right) => {
+                let mut sum = left.apply(vector)?;
+                let other = right.apply(vector)?;
+                if sum.len() != other.len() {
+                    return Err("Mismatched dimensions".into());
+                }
+                sum.add(&other);
+                Ok(sum)
+            }
+            Observable::Product(left, right) => left.apply(&right.apply(vector)?),
+        }
+    }
+}
+impl Add for Observable {
+    type Output = Observable;
+
+    fn add(self, rhs: Observable) -> Self::Output {
+        Observable::Sum(Box::new(self), Box::new(rhs))
+    }
+}
+impl Sub for Observable {
+    type Output = Observable;
+
+    fn sub(self, rhs: Observable) -> Self::Output {
+        self + rhs.scaled_by(Complex::from(-1.0))
+    }
+}
+impl Mul for Observable {
+    type Output = Observable;
+
+    fn mul(self, rhs: Observable) -> Self::Output {
+        Observable::Product(Box::new(self), Box::new(rhs))
+    }
+}
+
+fn diagonal(values: impl Iterator<Item = Complex>) -> SparseMatrix {
+    let triplets: Vec<(usize, usize, Complex)> = values.enumerate().map(|(i, value)| (i, i, value)).collect();
+    SparseMatrix::from_triplets(triplets.len(), triplets.len(), &triplets).unwrap()
+}
+
+/// Constant tridiagonal matrix with the given entries below, on and above the diagonal
+fn stencil(size: usize, lower: Complex, center: Complex, upper: Complex) -> SparseMatrix {
+    let mut triplets = vec![];
+    for i in 0..size {
+        if i > 0 {
+            triplets.push((i, i - 1, lower));
+        }
+        triplets.push((i, i, center));
+        if i + 1 < size {
+            triplets.push((i, i + 1, upper));
+        }
+    }
+    SparseMatrix::from_triplets(size, size, &triplets).unwrap()
+}
+
+/// Rows handled per task when combining basis vectors
+const ROW_BLOCK: usize = 256;
+
+/// Orthonormal eigenvectors stored as the contiguous columns of one matrix,
+/// with their energies, so a superposition is a single matrix–vector product
+#[derive(Debug, Clone)]
+pub struct EigenBasis {
+    size: usize,
+    energies: Vec<f64>,
+    /// Column-major, with state `k` at `vectors[k * size..(k + 1) * size]`
+    vectors: Vec<Complex>,
+}
+impl EigenBasis {
+    pub fn new(eigenpairs: Vec<(Complex, Vector)>) -> Result<Self, String> {
+        let size = eigenpairs.first().map_or(0, |(_, vector)| vector.len());
+        if eigenpairs.iter().any(|(_, vector)| vector.len() != size) {
+            return Err("Mismatched dimensions".into());
+        }
+
+        let mut energies = Vec::with_capacity(eigenpairs.len());
+        let mut vectors = Vec::with_capacity(eigenpairs.len() * size);
+        for (energy, vector) in eigenpairs {
+            energies.push(energy.real);
+            vectors.extend(vector.0);
+        }
+        Ok(Self { size, energies, vectors })
+    }
+
+    /// Length of each basis vector
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Number of basis vectors
+    pub fn count(&self) -> usize {
+        self.energies.len()
+    }
+
+    pub fn energies(&self) -> &[f64] {
+        &self.energies
+    }
+
+    pub fn vector(&self, k: usize) -> Option<&[Complex]> {
+        if k >= self.count() {
+            return None;
+        }
+        self.vectors.get(k * self.size..(k + 1) * self.size)
+    }
+
+    /// Coefficients of `state` in this basis
+    pub fn project(&self, state: &Vector) -> Result<Vector, String> {
+        if state.len() != self.size {
+            return Err("Mismatched dimensions".into());
+        }
+
+        Ok(Vector(
+            self.vectors
+                .par_chunks(self.size.max(1))
+                .map(|column| column.iter().zip(state.iter()).map(|(v, s)| v.conjugate() * *s).sum())
+                .collect(),
+        ))
+    }
+
+    /// Sum of `weights[k]` times state `k`, split by blocks of rows
+    pub fn combine(&self, weights: &[Complex]) -> Result<Vector, String> {
+        if weights.len() != self.count() {
+            return Err("Mismatched dimensions".into());
+        }
+
+        let columns: Vec<(usize, Complex)> = weights
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|(_, weight)| !weight.is_zero())
+            .collect();
+        let mut result = Vector::new(self.size);
+        result.0.par_chunks_mut(ROW_BLOCK).enumerate().for_each(|(block, rows)| {
+            let offset = block * ROW_BLOCK;
+            for &(k, weight) in &columns {
+                let column = &self.vectors[k * self.size + offset..][..rows.len()];
+                for (value, entry) in rows.iter_mut().zip(column) {
+                    *value += weight * *entry;
+                }
+            }
+        });
+        Ok(result)
+    }
+
+    /// The state with the given coefficients after evolving for `time`
+    pub fn frame(&self, coefficients: &Vector, time: f64) -> Result<Vector, String> {
+        if coefficients.len() != self.count() {
+            return Err("Mismatched dimensions".into());
+        }
+
+        let weights = coefficients
+            .iter()
+            .zip(&self.energies)
+            .map(|(c, energy)| {
+                let phase = -energy * time;
+                if c.is_zero() {
+                    Ok(Complex::zero())
+                } else if phase.is_finite() {
+                    Ok(*c * Complex::from_polar_radians(1.0, phase))
+                } else {
+                    Err(format!("The phase of energy {} overflows at t = {}", energy, time))
+                }
+            })
+            .collect::<Result<Vec<Complex>, String>>()?;
+        self.combine(&weights)
+    }
+
+    /// Many frames at once, one per entry of `times`
+    pub fn frames(&self, coefficients: &Vector, times: &[f64]) -> Result<Vec<Vector>, String> {
+        times.par_iter().map(|time| self.frame(coefficients, *time)).collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Vector(Vec<Complex>);
+impl Vector {
+    pub fn new(n: usize) -> Self {
+        Self(vec![Complex::zero(); n])
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Complex> {
+        self.0.iter()
+    }
+
+    pub fn to_vec(self) -> Vec<Complex> {
+        self.0
+    }
+
+    fn scale(&mut self, scalar: Complex) {
+        self.0.iter_mut().for_each(|value| *value *= scalar)
+    }
+
+    pub fn scaled_by(&self, scalar: Complex) -> Vector {
+        let mut new = vec![];
+        for i in 0..self.len() {
+            new.push(self[i] * scalar);
+        }
+        Vector(new)
+    }
+
+    /// Scales to unit length, dividing by the largest entry first so the sum of
+    /// squares can't overflow or underflow. Zero and non-finite vectors are left alone
+    pub fn normalize(&mut self) {
+        let largest = self.iter().map(|value| value.modulus()).fold(0.0, f64::max);
+        if largest == 0.0 || !largest.is_finite() {
+            return
+        }
+        self.0.iter_mut().for_each(|value| *value /= largest);
+        let length = self.inner_product(self).unwrap().sqrt();
+        self.scale(length.inverse())
+    }
+
+    pub fn inner_product(&self, other: &Vector) -> Result<Complex, String> {
+        if self.len() != other.len() {
+            return Err("Mismatched dimensions".into());
+        }
+
+        Ok(self
+            .iter()
+            .enumerate()
+            .map(|(i, value)| value.conjugate() * other[i])
+            .sum::<Complex>())
+    }
+
+    pub fn add(&mut self, other: &Vector) {
+        self.0.iter_mut().zip(other.iter()).for_each(|(value, other)| *value += *other)
+    }
+
+    /// `self += alpha * x`, in place
+    pub fn axpy(&mut self, alpha: Complex, x: &Vector) {
+        self.0.iter_mut().zip(x.iter()).for_each(|(value, x)| *value += alpha * *x)
+    }
+}
+
+impl Index<usize> for Vector {
+    type Output = Complex;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.0[index]
+    }
+}
+impl From<Vec<Complex>> for Vector {
+    fn from(vec: Vec<Complex>) -> Self {
+        Self(vec)
+    }
+}
+impl From<DVector<num_complex::Complex<f64>>> for Vector {
+    fn from(vector: DVector<num_complex::Complex<f64>>) -> Self {
+        let entries: Vec<num_complex::Complex<f64>> = vector.data.into();
+        Self(entries.into_iter().map(Complex::from).collect())
+    }
+}
+impl From<Vector> for DVector<num_complex::Complex<f64>> {
+    fn from(vector: Vector) -> Self {
+        DVector::from_vec(vector.0.into_iter().map(num_complex::Complex::from).collect())
+    }
+}
+impl From<&Vector> for DVector<num_complex::Complex<f64>> {
+    fn from(vector: &Vector) -> Self {
+        DVector::from_iterator(vector.len(), vector.iter().map(|value| (*value).into()))
+    }
+}
+impl From<DVector<f64>> for Vector {
+    fn from(vector: DVector<f64>) -> Self {
+        let entries: Vec<f64> = vector.data.into();
+        Self(entries.into_iter().map(Complex::from).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    /// The second-difference matrix tridiag(-1, 2, -1), whose spectrum is
+    /// `2 - 2 cos(k pi / (n + 1))` with eigenvectors `sin(j k pi / (n + 1))`
+    fn second_difference(n: usize) -> TridiagonalMatrix {
+        TridiagonalMatrix::from_diagonals(
+            vec![Complex::from(-1.0); n - 1],
+            vec![Complex::from(2.0); n],
+            vec![Complex::from(-1.0); n - 1],
+        )
+        .unwrap()
+    }
+
+    fn assert_orthonormal(eigenpairs: &[(Complex, Vector)]) {
+        for (i, (_, u)) in eigenpairs.iter().enumerate() {
+            for (j, (_, v)) in eigenpairs.iter().enumerate() {
+                let expected = if i == j { 1.0 } else { 0.0 };
+                let product = u.inner_product(v).unwrap();
+                assert!((product - expected).modulus() < 1e-10, "<{}|{}> = {:?}", i, j, product);
+            }
+        }
+    }
+
+    fn assert_sorted(eigenpairs: &[(Complex, Vector)]) {
+        assert!(eigenpairs.windows(2).all(|pair| pair[0].0.real <= pair[1].0.real));
+    }
+
+    fn assert_toeplitz_spectrum(eigenpairs: &[(Complex, Vector)], n: usize) {
+        assert_eq!(eigenpairs.len(), n);
+        for (index, (value, vector)) in eigenpairs.iter().enumerate() {
+            let k = (index + 1) as f64;
+            let angle = k * PI / (n + 1) as f64;
+            assert!((value.real - (2.0 - 2.0 * angle.cos())).abs() < 1e-12, "eigenvalue {}", k);
+            assert!(value.imag.abs() < 1e-12);
+
+            // Same line as the analytic mode, up to a global phase
+            let mut mode = Vector((1..=n).map(|j| Complex::from((j as f64 * angle).sin())).collect());
+            mode.normalize();
+            let overlap = mode.inner_product(vector).unwrap().modulus();
+            assert!((overlap - 1.0).abs() < 1e-10, "eigenvector {}", k);
+        }
+    }
+
+    #[test]
+    fn tridiagonal_eigenproblem_matches_particle_in_a_box() {
+        let n = 40;
+        let eigenpairs = second_difference(n).eigenproblem(30).unwrap();
+        assert_toeplitz_spectrum(&eigenpairs, n);
+        assert_orthonormal(&eigenpairs);
+    }
+
+    #[test]
+    fn dense_eigenproblem_matches_particle_in_a_box() {
+        let n = 25;
+        let mut matrix = Matrix::new(n, n);
+        for i in 0..n {
+            matrix.set(i, i, Complex::from(2.0));
+            if i + 1 < n {
+                matrix.set(i, i + 1, Complex::from(-1.0));
+                matrix.set(i + 1, i, Complex::from(-1.0));
+            }
+        }
+        let eigenpairs = matrix.eigenproblem(30).unwrap();
+        assert_toeplitz_spectrum(&eigenpairs, n);
+        assert_orthonormal(&eigenpairs);
+    }
+
+    #[test]
+    fn complex_hermitian_eigenproblem() {
+        let entries = [
+            [(4.0, 0.0), (1.0, -2.0), (0.0, 0.5), (-1.0, 1.0)],
+            [(1.0, 2.0), (-3.0, 0.0), (2.0, 1.0), (0.0, -1.5)],
+            [(0.0, -0.5), (2.0, -1.0), (1.0, 0.0), (0.5, 0.5)],
+            [(-1.0, -1.0), (0.0, 1.5), (0.5, -0.5), (0.0, 0.0)],
+        ];
+        let n = entries.len();
+        let mut matrix = Matrix::new(n, n);
+        for (row, values) in entries.iter().enumerate() {
+            for (column, &(real, imag)) in values.iter().enumerate() {
+                matrix.set(row, column, Complex::new(real, imag));
+            }
+        }
+
+        let eigenpairs = matrix.eigenproblem(30).unwrap();
+        assert_eq!(eigenpairs.len(), n);
+        assert_sorted(&eigenpairs);
+        assert_orthonormal(&eigenpairs);
+        for (value, vector) in &eigenpairs {
+            assert!(value.imag.abs() < 1e-12);
+            let image = matrix.apply(vector).unwrap();
+            for i in 0..n {
+                assert!((image[i] - *value * vector[i]).modulus() < 1e-10);
+            }
+        }
+        // The spectrum sums to the trace
+        let trace: f64 = eigenpairs.iter().map(|(value, _)| value.real).sum();
+        assert!((trace - 2.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn eigenproblem_rejects_non_finite_entries() {
+        let mut matrix = Matrix::new(2, 2);
+        matrix.set(0, 0, Complex::from(f64::NAN));
+        matrix.set(1, 1, Complex::from(1.0));
+        assert!(matrix.eigenproblem(30).is_err());
+
+        let mut tridiagonal = second_difference(3);
+        tridiagonal.set(1, 1, Complex::from(f64::INFINITY));
+        assert!(tridiagonal.eigenproblem(30).is_err());
+    }
+
+    #[test]
+    fn frames_with_overflowing_phases_are_errors() {
+        let basis = EigenBasis::new(second_difference(4).eigenproblem(30).unwrap()).unwrap();
+        let coefficients = Vector(vec![Complex::from(0.5); 4]);
+        assert!(basis.frame(&coefficients, 1e6).is_ok());
+        assert!(basis.frame(&coefficients, f64::MAX).is_err());
+        assert!(basis.frames(&coefficients, &[0.0, f64::INFINITY]).is_err());
+    }
+
+    fn dense(tridiagonal: &TridiagonalMatrix) -> Matrix {
+        let n = tridiagonal.size();
+        let mut matrix = Matrix::new(n, n);
+        for row in 0..n {
+            for column in 0..n {
+                matrix.set(row, column, tridiagonal.get(row, column).unwrap());
+            }
+        }
+        matrix
+    }
+
+    fn assert_close(actual: &Vector, expected: &Vector, tolerance: f64) {
+        assert_eq!(actual.len(), expected.len());
+        for i in 0..actual.len() {
+            assert!((actual[i] - expected[i]).modulus() <= tolerance, "{}: {:?} != {:?}", i, actual[i], expected[i]);
+        }
+    }
+
+    #[test]
+    fn expm_matches_closed_forms() {
+        // exp(-iθσx) = cos θ - i sin θ σx, at angles that need no scaling up to many squarings
+        for &theta in &[1e-3, 0.1, 1.0, 10.0, 1000.0] {
+            let mut rotation = Matrix::new(2, 2);
+            rotation.set(0, 1, Complex::new(0.0, -theta));
+            rotation.set(1, 0, Complex::new(0.0, -theta));
+            let result = expm(&rotation).unwrap();
+            let (cos, sin) = (Complex::from(theta.cos()), Complex::new(0.0, -theta.sin()));
+            let tolerance = 1e-13 * theta.max(1.0);
+            for (row, column, expected) in [(0, 0, cos), (0, 1, sin), (1, 0, sin), (1, 1, cos)] {
+                let entry = result.get(row, column).unwrap();
+                assert!((entry - expected).modulus() < tolerance, "θ = {}: {:?} != {:?}", theta, entry, expected);
+            }
+        }
+
+        let diagonal = [Complex::new(1.0, 2.0), Complex::from(-3.0), Complex::new(0.0, 0.5), Complex::from(8.0)];
+        let mut matrix = Matrix::new(4, 4);
+        for (i, value) in diagonal.iter().enumerate() {
+            matrix.set(i, i, *value);
+        }
+        let result = expm(&matrix).unwrap();
+        for (i, value) in diagonal.iter().enumerate() {
+            for j in 0..4 {
+                let expected = if i == j { value.exp() } else { Complex::zero() };
+                let entry = result.get(i, j).unwrap();
+                assert!((entry - expected).modulus() < 1e-12 * expected.modulus().max(1.0));
+            }
+        }
+
+        assert!(expm(&Matrix::new(2, 3)).is_err());
+        let mut infinite = Matrix::identity(2);
+        infinite.set(0, 1, Complex::from(f64::INFINITY));
+        assert!(expm(&infinite).is_err());
+    }
+
+    /// Both propagators against the eigenbasis, on an oscillator with V = x²/100
+    #[test]
+    fn propagators_agree_on_a_harmonic_hamiltonian() {
+        let n = 60;
+        let potential = Vector((0..n).map(|j| Complex::from((j as f64 - 30.0).powi(2) / 100.0)).collect());
+        let hamiltonian = crate::experiment::hamiltonian(&potential);
+        let mut state = Vector((0..n).map(|j| Complex::from((-(j as f64 - 24.0).powi(2) / 16.0).exp())).collect());
+        state.normalize();
+
+        let basis = EigenBasis::new(hamiltonian.eigenproblem(30).unwrap()).unwrap();
+        let coefficients = basis.project(&state).unwrap();
+        let time = 7.5;
+        let expected = basis.frame(&coefficients, time).unwrap();
+
+        let mut generator = dense(&hamiltonian);
+        generator.entries.iter_mut().for_each(|value| *value *= Complex::new(0.0, -time));
+        assert_close(&expm(&generator).unwrap().apply(&state).unwrap(), &expected, 1e-10);
+        assert_close(&krylov_propagate(&hamiltonian, &state, time, 1e-10).unwrap(), &expected, 1e-8);
+        let backwards = basis.frame(&coefficients, -time).unwrap();
+        assert_close(&krylov_propagate(&hamiltonian, &state, -time, 1e-10).unwrap(), &backwards, 1e-8);
+        assert_close(&krylov_propagate(&hamiltonian, &state, 0.0, 1e-10).unwrap(), &state, 0.0);
+    }
+
+    /// Deterministic entries with no structure to speak of
+    fn entry(row: usize, column: usize) -> Complex {
+        Complex::new(((row * 7 + column * 3) % 11) as f64 - 5.0, ((row * 5 + column * 2) % 7) as f64 - 3.0)
+    }
+
+    fn assert_round_trip(operator: &impl LinearOperator, solve: impl Fn(&Vector) -> Result<Vector, String>) {
+        let (n, _) = operator.shape();
+        let x = Vector((0..n).map(|i| Complex::new(i as f64 - 2.5, 1.0 / (i + 1) as f64)).collect());
+        let solution = solve(&operator.apply(&x).unwrap()).unwrap();
+        assert_close(&solution, &x, 1e-10);
+        assert!(solve(&Vector::new(n + 1)).is_err());
+        assert!(operator.apply(&Vector::new(n + 1)).is_err());
+    }
+
+    #[test]
+    fn tridiagonal_solve_inverts_apply() {
+        let n = 12;
+        let mut matrix = TridiagonalMatrix::new(n);
+        for i in 0..n {
+            matrix.set(i, i, entry(i, i) + Complex::from(20.0));
+            if i + 1 < n {
+                matrix.set(i, i + 1, entry(i, i + 1));
+                matrix.set(i + 1, i, entry(i + 1, i));
+            }
+        }
+        assert_round_trip(&matrix, |rhs| matrix.solve(rhs));
+
+        let mut singular = TridiagonalMatrix::new(2);
+        for (row, column) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+            singular.set(row, column, Complex::from(1.0));
+        }
+        assert!(singular.solve(&Vector::new(2)).is_err());
+        assert!(!singular.set(0, 2, Complex::from(1.0)));
+    }
+
+    #[test]
+    fn banded_solve_pivots() {
+        let n = 10;
+        let mut matrix = BandedMatrix::new(n, 2, 1);
+        for row in 0..n {
+            for column in row.saturating_sub(2)..n.min(row + 2) {
+                assert!(matrix.set(row, column, entry(row, column)));
+            }
+        }
+        // Without pivoting the first step would divide by zero
+        matrix.set(0, 0, Complex::zero());
+        assert!(!matrix.set(0, 2, Complex::from(1.0)));
+        assert_eq!(matrix.get(0, 5), Some(Complex::zero()));
+        assert_round_trip(&matrix, |rhs| matrix.solve(rhs));
+
+        let banded = BandedMatrix::from(&second_difference(6));
+        assert_round_trip(&banded, |rhs| banded.solve(rhs));
+
+        // A zero column leaves nothing to pivot on
+        let mut singular = BandedMatrix::new(4, 1, 1);
+        for i in 0..4 {
+            singular.set(i, i, Complex::from(1.0));
+        }
+        singular.set(2, 2, Complex::zero());
+        assert!(singular.solve(&Vector::new(4)).is_err());
+    }
+
+    #[test]
+    fn sparse_apply_matches_dense() {
+        let (rows, columns) = (5, 7);
+        let mut triplets = vec![];
+        let mut dense = Matrix::new(rows, columns);
+        for row in 0..rows {
+            for column in (row % 3..columns).step_by(3) {
+                triplets.push((row, column, entry(row, column)));
+                dense.set(row, column, entry(row, column));
+            }
+        }
+        // Repeated positions add up
+        triplets.push((4, 1, Complex::from(2.0)));
+        triplets.push((4, 1, Complex::from(3.0)));
+        dense.set(4, 1, entry(4, 1) + Complex::from(5.0));
+
+        let sparse = SparseMatrix::from_triplets(rows, columns, &triplets).unwrap();
+        assert_eq!(sparse.shape(), (rows, columns));
+        assert_eq!(sparse.get(4, 1), dense.get(4, 1));
+        assert_eq!(sparse.get(0, 1), Some(Complex::zero()));
+        assert_eq!(sparse.get(rows, 0), None);
+        let x = Vector((0..columns).map(|i| Complex::new(1.0, i as f64)).collect());
+        assert_close(&sparse.apply(&x).unwrap(), &dense.apply(&x).unwrap(), 1e-12);
+        assert!(sparse.apply(&Vector::new(rows)).is_err());
+        assert!(SparseMatrix::from_triplets(2, 2, &[(2, 0, Complex::from(1.0))]).is_err());
+
+        let tridiagonal = second_difference(5);
+        let x = Vector((0..5).map(|i| Complex::from(i as f64)).collect());
+        assert_close(&SparseMatrix::from(&tridiagonal).apply(&x).unwrap(), &tridiagonal.apply(&x).unwrap(), 0.0);
+    }
+
+    #[test]
+    fn krylov_gives_up_on_unreachable_tolerances() {
+        let hamiltonian = second_difference(50);
+        let mut state = Vector((0..50).map(|j| Complex::from(((j * j) % 7) as f64)).collect());
+        state.normalize();
+        assert!(krylov_propagate(&hamiltonian, &state, 100.0, 1e-300).is_err());
+        assert!(krylov_propagate(&hamiltonian, &state, 100.0, f64::EPSILON / 2.0).is_err());
+        assert!(krylov_propagate(&hamiltonian, &state, 100.0, 0.0).is_err());
+        assert!(krylov_propagate(&hamiltonian, &state, f64::NAN, 1e-8).is_err());
+        assert!(krylov_propagate(&second_difference(3), &state, 1.0, 1e-8).is_err());
+    }
+
+    /// Columns of `operator`, found by applying it to each unit vector
+    fn columns(operator: &impl LinearOperator) -> Vec<Vector> {
+        let (_, n) = operator.shape();
+        (0..n)
+            .map(|j| {
+                let unit = Vector((0..n).map(|i| Complex::from(if i == j { 1.0 } else { 0.0 })).collect());
+                operator.apply(&unit).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn observables_are_hermitian() {
+        let (n, start, spacing) = (12, -1.5, 0.25);
+        let potential = Vector((0..n).map(|i| Complex::from((start + i as f64 * spacing).powi(2))).collect());
+        let observables = [
+            Observable::position(n, start, spacing),
+            Observable::momentum(n, spacing),
+            Observable::momentum_squared(n, spacing),
+            Observable::hamiltonian(&potential, spacing),
+        ];
+        for observable in &observables {
+            let columns = columns(observable);
+            for (j, column) in columns.iter().enumerate() {
+                for (i, value) in column.iter().enumerate() {
+                    let adjoint = columns[i][j].conjugate();
+                    assert!((*value - adjoint).modulus() < 1e-12, "{:?} at ({}, {})", observable, i, j);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn gaussians_saturate_the_uncertainty_bound() {
+        let (n, start, spacing) = (400, -10.0, 0.05);
+        let mut state =
+            Vector((0..n).map(|i| Complex::from((-(start + i as f64 * spacing).powi(2) / 2.0).exp())).collect());
+        state.normalize();
+        let position = Observable::position(n, start, spacing);
+        let momentum = Observable::momentum(n, spacing);
+
+        let bound = position.uncertainty_bound(&momentum, &state).unwrap();
+        assert!((bound - 0.5).abs() < 1e-3, "{}", bound);
+        let product = position.uncertainty(&state).unwrap() * momentum.uncertainty(&state).unwrap();
+        assert!(product >= bound && product - bound < 1e-3, "{} < {}", product, bound);
+    }
+
+    #[test]
+    fn unit_spacing_hamiltonian_matches_the_experiment() {
+        let potential = Vector((0..9).map(|i| Complex::from((i as f64 - 4.0).powi(2) / 3.0)).collect());
+        let expected = columns(&crate::experiment::hamiltonian(&potential));
+        for (actual, expected) in columns(&Observable::hamiltonian(&potential, 1.0)).iter().zip(&expected) {
+            assert_close(actual, expected, 1e-15);
+        }
+    }
+
+    #[test]
+    fn nalgebra_round_trip() {
+        let vector = Vector(vec![Complex::new(1.0, -2.0), Complex::new(-0.0, 3.5), Complex::new(1e-300, 1e300)]);
+        let borrowed = DVector::from(&vector);
+        let owned = DVector::from(vector.clone());
+        assert_eq!(borrowed, owned);
+        assert_eq!(owned[1], num_complex::Complex::new(-0.0, 3.5));
+        assert_eq!(Vector::from(owned).0, vector.0);
+
+        let real = Vector::from(DVector::from_vec(vec![0.25, -4.0]));
+        assert_eq!(real.0, [Complex::from(0.25), Complex::from(-4.0)]);
+        assert_eq!(Vector::from(DVector::<f64>::zeros(0)).len(), 0);
+    }
+}
@@ -0,0 +1,448 @@
+//! Static figures as SVG, built from the same `Datapoints` the GUI draws
+
+use crate::datapoints::{Datapoint, Datapoints};
+use crate::experiment::Experiment;
+use std::{
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+const WIDTH: f64 = 640.0;
+const HEIGHT: f64 = 420.0;
+/// Space around the plot area for the title, tick labels and axis labels
+const MARGIN: (f64, f64, f64, f64) = (40.0, 20.0, 55.0, 75.0);
+const FONT: &str = "font-family=\"Helvetica, Arial, sans-serif\"";
+const COLORS: [&str; 8] = ["#1f77b4", "#d62728", "#2ca02c", "#9467bd", "#ff7f0e", "#8c564b", "#e377c2", "#17becf"];
+const POTENTIAL_COLOR: &str = "#333333";
+const ENERGY: &str = "Energy (ħ²/2mΔx²)";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stroke {
+    Solid,
+    Dashed,
+}
+
+/// A labelled curve. Non-finite points break it into pieces
+#[derive(Debug, Clone, PartialEq)]
+pub struct Series {
+    pub label: Option<String>,
+    pub points: Datapoints,
+    pub color: String,
+    pub stroke: Stroke,
+}
+
+/// A line plot with axes, ticks and a legend
+#[derive(Debug, Clone, PartialEq)]
+pub struct Plot {
+    pub title: String,
+    pub x_label: String,
+    pub y_label: String,
+    pub series: Vec<Series>,
+    /// Fixed vertical extent, fitted to the data when `None`
+    pub y_range: Option<(f64, f64)>,
+}
+impl Plot {
+    pub fn new(title: &str, x_label: &str, y_label: &str) -> Self {
+        Self { title: title.into(), x_label: x_label.into(), y_label: y_label.into(), series: vec![], y_range: None }
+    }
+
+    /// Adds a curve, coloured after the ones already there
+    pub fn line(mut self, label: Option<&str>, points: Datapoints, stroke: Stroke) -> Self {
+        let color = COLORS[self.series.len() % COLORS.len()].to_string();
+        self.series.push(Series { label: label.map(String::from), points, color, stroke });
+        self
+    }
+
+    pub fn colored_line(mut self, label: Option<&str>, points: Datapoints, stroke: Stroke, color: &str) -> Self {
+        self.series.push(Series { label: label.map(String::from), points, color: color.into(), stroke });
+        self
+    }
+
+    /// The smallest box around every finite point, never empty
+    fn bounds(&self) -> ((f64, f64), (f64, f64)) {
+        let finite = self
+            .series
+            .iter()
+            .flat_map(|series| &series.points.values)
+            .filter(|point| point.x.is_finite() && point.y.is_finite());
+        let (mut x, mut y) = ((f64::INFINITY, f64::NEG_INFINITY), (f64::INFINITY, f64::NEG_INFINITY));
+        for point in finite {
+            x = (x.0.min(point.x as f64), x.1.max(point.x as f64));
+            y = (y.0.min(point.y as f64), y.1.max(point.y as f64));
+        }
+        let widen = |(low, high): (f64, f64)| {
+            if low > high {
+                (0.0, 1.0)
+            } else if low == high {
+                (low - 0.5 * low.abs().max(1.0), high + 0.5 * high.abs().max(1.0))
+            } else {
+                (low, high)
+            }
+        };
+        let (x, (low, high)) = match self.y_range {
+            Some((low, high)) if low < high => (widen(x), (low, high)),
+            _ => (widen(x), widen(y)),
+        };
+        // Keeps curves off the top and bottom edges
+        let padding = 0.05 * (high - low);
+        (x, (low - padding, high + padding))
+    }
+
+    pub fn to_svg(&self) -> String {
+        let (top, right, bottom, left) = MARGIN;
+        let (area_width, area_height) = (WIDTH - left - right, HEIGHT - top - bottom);
+        let ((x_low, x_high), (y_low, y_high)) = self.bounds();
+        let to_x = |x: f64| left + (x - x_low) / (x_high - x_low) * area_width;
+        let to_y = |y: f64| top + (y_high - y) / (y_high - y_low) * area_height;
+
+        let mut svg = String::new();
+        let mut line = |text: String| {
+            svg.push_str(&text);
+            svg.push('\n');
+        };
+        line(format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">",
+            WIDTH, HEIGHT, WIDTH, HEIGHT
+        ));
+        line(format!("<rect width=\"{}\" height=\"{}\" fill=\"white\"/>", WIDTH, HEIGHT));
+        line(format!(
+            "<clipPath id=\"area\"><rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"/></clipPath>",
+            left, top, area_width, area_height
+        ));
+
+        // Grid lines and tick labels
+        for (tick, label) in ticks(x_low, x_high) {
+            let x = to_x(tick);
+            line(format!(
+                "<line x1=\"{x:.2}\" y1=\"{}\" x2=\"{x:.2}\" y2=\"{}\" stroke=\"#e0e0e0\"/>",
+                top,
+                top + area_height,
+                x = x
+            ));
+            line(format!(
+                "<text x=\"{:.2}\" y=\"{:.2}\" {} font-size=\"11\" text-anchor=\"middle\">{}</text>",
+                x,
+                top + area_height + 16.0,
+                FONT,
+                escape(&label)
+            ));
+        }
+        for (tick, label) in ticks(y_low, y_high) {
+            let y = to_y(tick);
+            line(format!(
+                "<line x1=\"{}\" y1=\"{y:.2}\" x2=\"{}\" y2=\"{y:.2}\" stroke=\"#e0e0e0\"/>",
+                left,
+                left + area_width,
+                y = y
+            ));
+            line(format!(
+                "<text x=\"{:.2}\" y=\"{:.2}\" {} font-size=\"11\" text-anchor=\"end\">{}</text>",
+                left - 6.0,
+                y + 4.0,
+                FONT,
+                escape(&label)
+            ));
+        }
+        line(format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"none\" stroke=\"black\"/>",
+            left, top, area_width, area_height
+        ));
+
+        for series in &self.series {
+            let dash = match series.stroke {
+                Stroke::Solid => "",
+                Stroke::Dashed => " stroke-dasharray=\"6 4\"",
+            };
+            for piece in series.points.values.split(|point| !point.x.is_finite() || !point.y.is_finite()) {
+                if piece.is_empty() {
+                    continue;
+                }
+                let mut points = String::new();
+                for Datapoint { x, y } in piece {
+                    // Writing to a String can't fail
+                    let _ = write!(points, "{:.2},{:.2} ", to_x(*x as f64), to_y(*y as f64));
+                }
+                line(format!(
+                    "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"1.5\"{} clip-path=\"url(#area)\"/>",
+                    points.trim_end(),
+                    series.color,
+                    dash
+                ));
+            }
+        }
+
+        // Legend in the top right corner of the plot area
+        let labelled: Vec<_> =
+            self.series.iter().filter_map(|series| series.label.as_ref().map(|label| (series, label))).collect();
+        if !labelled.is_empty() {
+            line(format!(
+                "<rect x=\"{:.2}\" y=\"{:.2}\" width=\"146\" height=\"{:.2}\" fill=\"white\" fill-opacity=\"0.85\"/>",
+                left + area_width - 154.0,
+                top + 4.0,
+                16.0 * labelled.len() as f64 + 6.0
+            ));
+        }
+        for (row, (series, label)) in labelled.into_iter().enumerate() {
+            let y = top + 16.0 + 16.0 * row as f64;
+            let x = left + area_width - 150.0;
+            let dash = if series.stroke == Stroke::Dashed { " stroke-dasharray=\"6 4\"" } else { "" };
+            line(format!(
+                "<line x1=\"{:.2}\" y1=\"{:.2}\" x2=\"{:.2}\" y2=\"{:.2}\" stroke=\"{}\" stroke-width=\"1.5\"{}/>",
+                x,
+                y - 4.0,
+                x + 24.0,
+                y - 4.0,
+                series.color,
+                dash
+            ));
+            line(format!(
+                "<text x=\"{:.2}\" y=\"{:.2}\" {} font-size=\"11\">{}</text>",
+                x + 30.0,
+                y,
+                FONT,
+                escape(label)
+            ));
+        }
+
+        line(format!(
+            "<text x=\"{:.2}\" y=\"{:.2}\" {} font-size=\"15\" text-anchor=\"middle\">{}</text>",
+            left + area_width / 2.0,
+            top - 14.0,
+            FONT,
+            escape(&self.title)
+        ));
+        line(format!(
+            "<text x=\"{:.2}\" y=\"{:.2}\" {} font-size=\"12\" text-anchor=\"middle\">{}</text>",
+            left + area_width / 2.0,
+            HEIGHT - 14.0,
+            FONT,
+            escape(&self.x_label)
+        ));
+        line(format!(
+            "<text transform=\"translate({:.2} {:.2}) rotate(-90)\" {} font-size=\"12\" text-anchor=\"middle\">{}</text>",
+            18.0,
+            top + area_height / 2.0,
+            FONT,
+            escape(&self.y_label)
+        ));
+        line("</svg>".into());
+        svg
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, self.to_svg()).map_err(|error| format!("{}: {}", path.display(), error))
+    }
+}
+
+/// Round tick positions inside `[low, high]`, with labels precise enough to tell them apart
+fn ticks(low: f64, high: f64) -> Vec<(f64, String)> {
+    let raw = (high - low) / 6.0;
+    let magnitude = 10f64.powf(raw.log10().floor());
+    let step = magnitude
+        * match raw / magnitude {
+            r if r < 1.5 => 1.0,
+            r if r < 3.0 => 2.0,
+            r if r < 7.0 => 5.0,
+            _ => 10.0,
+        };
+    let decimals = (-step.log10().floor()).max(0.0) as usize;
+    let first = (low / step).ceil() as i64;
+    let last = (high / step).floor() as i64;
+    (first..=last)
+        .map(|k| {
+            let tick = k as f64 * step;
+            // Avoids labels like -0.0
+            let tick = if tick.abs() < step * 1e-9 { 0.0 } else { tick };
+            (tick, format!("{:.*}", decimals, tick))
+        })
+        .collect()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+fn potential(experiment: &Experiment) -> Datapoints {
+    Datapoints::on_grid(experiment.grid(), experiment.potential().iter().map(|value| value.real))
+}
+
+pub fn potential_plot(experiment: &Experiment) -> Plot {
+    Plot::new("Potential", "x", ENERGY).colored_line(Some("V(x)"), potential(experiment), Stroke::Solid, POTENTIAL_COLOR)
+}
+
+/// The real part of each eigenstate, drawn around a dashed line at its energy
+/// over the potential, scaled so neighbouring states don't overlap
+pub fn eigenstates_plot(experiment: &Experiment, indices: &[usize]) -> Result<Plot, String> {
+    let mut states = Vec::with_capacity(indices.len());
+    for &n in indices {
+        let vector = experiment
+            .eigenvector(n)
+            .ok_or(format!("There is no eigenvector {}, only {}", n, experiment.basis().count()))?;
+        states.push((n, experiment.eigenvalues()[n], vector));
+    }
+
+    let mut energies: Vec<f64> = states.iter().map(|(_, energy, _)| *energy).collect();
+    energies.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let gap = energies.windows(2).map(|pair| pair[1] - pair[0]).filter(|gap| *gap > 0.0).fold(f64::INFINITY, f64::min);
+    let gap = if gap.is_finite() { gap } else { energies.first().map_or(1.0, |energy| energy.abs().max(1.0)) };
+    let amplitude = states
+        .iter()
+        .flat_map(|(_, _, vector)| vector.iter().map(|value| value.real.abs()))
+        .fold(0.0, f64::max);
+    let scale = if amplitude > 0.0 { 0.4 * gap / amplitude } else { 0.0 };
+
+    // Walls much taller than the states shown would squash them flat
+    let lowest = experiment.potential().iter().map(|value| value.real).fold(f64::INFINITY, f64::min);
+    let (low, high) = (energies.first(), energies.last());
+    let y_range = low.zip(high).map(|(low, high)| (lowest.min(low - gap), high + gap));
+
+    let grid = experiment.grid();
+    let mut plot = Plot::new("Eigenstates", "x", ENERGY).colored_line(
+        Some("V(x)"),
+        potential(experiment),
+        Stroke::Solid,
+        POTENTIAL_COLOR,
+    );
+    for (n, energy, vector) in states {
+        let level = Datapoints::new(vec![Datapoint::new(grid.start(), energy), Datapoint::new(grid.end(), energy)]);
+        let state = Datapoints::on_grid(grid, vector.iter().map(|value| energy + scale * value.real));
+        let color = COLORS[n % COLORS.len()];
+        plot = plot
+            .colored_line(None, level, Stroke::Dashed, color)
+            .colored_line(Some(&format!("ψ{} (E = {:.4})", n, energy)), state, Stroke::Solid, color);
+    }
+    plot.y_range = y_range;
+    Ok(plot)
+}
+
+/// |ψ|² at each of `times`
+pub fn density_plot(experiment: &Experiment, times: &[f64]) -> Result<Plot, String> {
+    let mut plot = Plot::new("Probability density", "x", "|ψ|² (Σ|ψ|² = 1)");
+    for (time, frame) in times.iter().zip(experiment.evolve_many(times)?) {
+        let density = Datapoints::on_grid(experiment.grid(), frame.iter().map(|value| value.modulus_squared()));
+        plot = plot.line(Some(&format!("t = {}", time)), density, Stroke::Solid);
+    }
+    Ok(plot)
+}
+
+/// Writes `potential.svg`, `eigenstates.svg` and `density.svg` into `directory`,
+/// which is created if needed
+pub fn save_figures(
+    experiment: &Experiment,
+    directory: &Path,
+    eigenstates: &[usize],
+    times: &[f64],
+) -> Result<Vec<PathBuf>, String> {
+    fs::create_dir_all(directory).map_err(|error| format!("{}: {}", directory.display(), error))?;
+    let figures = [
+        ("potential.svg", potential_plot(experiment)),
+        ("eigenstates.svg", eigenstates_plot(experiment, eigenstates)?),
+        ("density.svg", density_plot(experiment, times)?),
+    ];
+    figures
+        .iter()
+        .map(|(name, plot)| {
+            let path = directory.join(name);
+            plot.save(&path).map(|_| path)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::experiment::{Grid, InitialState, Potential, Solver};
+
+    fn experiment() -> Experiment {
+        let potential = Potential::parse("x^2/10").unwrap();
+        let initial_state = InitialState::parse("e^(-x^2)").unwrap();
+        Solver::default().solve(Grid::new(-5.0, 5.0, 64).unwrap(), &potential, &initial_state, 0.0).unwrap()
+    }
+
+    /// Checks that `svg` parses, then calls `check` with its root element
+    fn with_svg(plot: &Plot, check: impl FnOnce(roxmltree::Node)) {
+        let svg = plot.to_svg();
+        let document = roxmltree::Document::parse(&svg).unwrap_or_else(|error| panic!("{}\n{}", error, svg));
+        let root = document.root_element();
+        assert_eq!(root.tag_name().name(), "svg");
+        assert_eq!(root.tag_name().namespace(), Some("http://www.w3.org/2000/svg"));
+        check(root)
+    }
+
+    fn texts<'a>(root: roxmltree::Node<'a, '_>) -> Vec<&'a str> {
+        root.children().filter(|node| node.has_tag_name("text")).filter_map(|node| node.text()).collect()
+    }
+
+    fn polylines<'a, 'input>(root: roxmltree::Node<'a, 'input>) -> Vec<roxmltree::Node<'a, 'input>> {
+        root.children().filter(|node| node.has_tag_name("polyline")).collect()
+    }
+
+    #[test]
+    fn figures_have_labelled_axes() {
+        let experiment = experiment();
+        let figures = [
+            (potential_plot(&experiment), "Potential", ENERGY),
+            (eigenstates_plot(&experiment, &[0, 1]).unwrap(), "Eigenstates", ENERGY),
+            (density_plot(&experiment, &[0.0, 1.5]).unwrap(), "Probability density", "|ψ|² (Σ|ψ|² = 1)"),
+        ];
+        for (plot, title, y_label) in &figures {
+            with_svg(plot, |root| {
+                let texts = texts(root);
+                assert!(texts.contains(title) && texts.contains(&"x") && texts.contains(y_label), "{:?}", texts);
+                // The frame of the plot area, and tick labels along both axes
+                let framed =
+                    |node: roxmltree::Node| node.has_tag_name("rect") && node.attribute("stroke") == Some("black");
+                assert!(root.children().any(framed));
+                let anchored = |anchor: &str| {
+                    root.children().filter(|node| node.attribute("text-anchor") == Some(anchor)).count()
+                };
+                assert!(anchored("end") >= 3 && anchored("middle") >= 3);
+            });
+        }
+    }
+
+    #[test]
+    fn eigenstates_get_one_path_each() {
+        let experiment = experiment();
+        let plot = eigenstates_plot(&experiment, &[0, 1, 2]).unwrap();
+        with_svg(&plot, |root| {
+            let polylines = polylines(root);
+            // The potential, then a dashed level and the state for each
+            assert_eq!(polylines.len(), 1 + 2 * 3);
+            assert_eq!(polylines[0].attribute("stroke"), Some(POTENTIAL_COLOR));
+            for (n, color) in COLORS.iter().enumerate().take(3) {
+                let solid = polylines
+                    .iter()
+                    .filter(|node| node.attribute("stroke") == Some(*color))
+                    .filter(|node| node.attribute("stroke-dasharray").is_none())
+                    .count();
+                assert_eq!(solid, 1, "ψ{}", n);
+                let label = format!("ψ{} (E = {:.4})", n, experiment.eigenvalues()[n]);
+                assert!(texts(root).contains(&label.as_str()));
+            }
+        });
+        assert!(eigenstates_plot(&experiment, &[1000]).is_err());
+    }
+
+    #[test]
+    fn gaps_split_lines_and_text_is_escaped() {
+        let points = [(0.0, 0.0), (1.0, 1.0), (2.0, f64::NAN), (3.0, 1.0), (4.0, 0.0)];
+        let points = Datapoints::new(points.iter().map(|(x, y)| Datapoint::new(*x, *y)).collect());
+        let plot = Plot::new("<b> & \"c\"", "x", "y").line(Some("a < b"), points, Stroke::Dashed);
+        with_svg(&plot, |root| {
+            assert_eq!(polylines(root).len(), 2);
+            assert!(polylines(root).iter().all(|node| node.attribute("stroke-dasharray") == Some("6 4")));
+            let texts = texts(root);
+            assert!(texts.contains(&"<b> & \"c\"") && texts.contains(&"a < b"), "{:?}", texts);
+        });
+    }
+
+    #[test]
+    fn ticks_are_round() {
+        let labels = |low, high| ticks(low, high).into_iter().map(|(_, label)| label).collect::<Vec<String>>();
+        assert_eq!(labels(0.0, 1.0), ["0.0", "0.2", "0.4", "0.6", "0.8", "1.0"]);
+        assert_eq!(labels(-5.0, 5.0), ["-4", "-2", "0", "2", "4"]);
+        assert_eq!(labels(-0.03, 0.03), ["-0.03", "-0.02", "-0.01", "0.00", "0.01", "0.02", "0.03"]);
+    }
+}
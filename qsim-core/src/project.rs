@@ -0,0 +1,198 @@
+use crate::experiment::{Experiment, Grid, InitialState, Potential, Solver};
+use crate::import::Interpolation;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{fs, path::Path};
+
+/// Written to every project file. Bump it when the format changes and add a
+/// step to `migrate` that upgrades the previous version
+pub const PROJECT_VERSION: u64 = 1;
+
+/// Everything needed to reopen an experiment exactly as it was set up
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Project {
+    pub potential: FunctionSource,
+    pub wavefunction: FunctionSource,
+    pub grid: Grid,
+    pub momentum: f64,
+    pub solver: Solver,
+}
+
+/// A function as entered by the user, with both the formula and the drawing
+/// kept so switching modes after loading loses nothing
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FunctionSource {
+    pub formula: String,
+    /// `(x, y)` pairs
+    pub datapoints: Vec<(f64, f64)>,
+    pub use_formula: bool,
+    /// How the datapoints are laid onto the grid
+    #[serde(default)]
+    pub interpolation: Interpolation,
+}
+
+impl Project {
+    pub fn potential(&self) -> Result<Potential, String> {
+        if self.potential.use_formula {
+            Potential::parse(&self.potential.formula).map_err(|error| format!("potential: {}", error))
+        } else {
+            Ok(Potential::Interpolated(self.potential.datapoints.clone(), self.potential.interpolation))
+        }
+    }
+
+    pub fn initial_state(&self) -> Result<InitialState, String> {
+        if self.wavefunction.use_formula {
+            InitialState::parse(&self.wavefunction.formula).map_err(|error| format!("wavefunction: {}", error))
+        } else {
+            Ok(InitialState::Interpolated(self.wavefunction.datapoints.clone(), self.wavefunction.interpolation))
+        }
+    }
+
+    pub fn solve(&self) -> Result<Experiment, String> {
+        self.solver.solve(self.grid, &self.potential()?, &self.initial_state()?, self.momentum)
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut value = serde_json::to_value(self).expect("projects always serialize");
+        value["version"] = json!(PROJECT_VERSION);
+        serde_json::to_string_pretty(&value).expect("projects always serialize")
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        Self::from_value(serde_json::from_str(json).map_err(|error| error.to_string())?)
+    }
+
+    /// Reads a project of any version up to `PROJECT_VERSION`
+    pub fn from_value(value: Value) -> Result<Self, String> {
+        serde_json::from_value(migrate(value)?).map_err(|error| error.to_string())
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, self.to_json()).map_err(|error| format!("{}: {}", path.display(), error))
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let json = fs::read_to_string(path).map_err(|error| format!("{}: {}", path.display(), error))?;
+        Self::from_json(&json).map_err(|error| format!("{}: {}", path.display(), error))
+    }
+}
+
+/// Upgrades a project file one version at a time. Files without a version are
+/// the experiment descriptions read by the `qsim` binary
+fn migrate(mut value: Value) -> Result<Value, String> {
+    let mut version = match value.get("version") {
+        None => 0,
+        Some(version) => version.as_u64().ok_or("Invalid project version")?,
+    };
+    if version > PROJECT_VERSION {
+        return Err(format!(
+            "Project version {} is newer than the supported version {}",
+            version, PROJECT_VERSION
+        ));
+    }
+
+    while version < PROJECT_VERSION {
+        value = match version {
+            0 => {
+                let formula = |key: &str| -> Result<Value, String> {
+                    let formula = value.get(key).and_then(Value::as_str).ok_or(format!("missing field `{}`", key))?;
+                    Ok(json!({ "formula": formula, "datapoints": [], "use_formula": true }))
+                };
+                json!({
+                    "potential": formula("potential")?,
+                    "wavefunction": formula("wavefunction")?,
+                    "grid": value.get("grid").cloned().ok_or("missing field `grid`")?,
+                    "momentum": value.get("momentum").cloned().unwrap_or(json!(0.0)),
+                    "solver": value.get("solver").cloned().unwrap_or(json!(Solver::default())),
+                })
+            }
+            _ => unreachable!(),
+        };
+        version += 1;
+    }
+
+    if let Value::Object(fields) = &mut value {
+        fields.remove("version");
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn formula(formula: &str) -> FunctionSource {
+        FunctionSource {
+            formula: formula.into(),
+            datapoints: vec![],
+            use_formula: true,
+            interpolation: Interpolation::Linear,
+        }
+    }
+
+    fn project() -> Project {
+        Project {
+            potential: FunctionSource {
+                formula: "step(x)".into(),
+                datapoints: vec![(-1.0, 0.0), (0.1, 2.5), (1.0, 2.5)],
+                use_formula: false,
+                interpolation: Interpolation::Monotone,
+            },
+            wavefunction: formula("e^(-(x+0.3)^2/0.01)"),
+            grid: Grid::new(-1.0, 1.0, 64).unwrap(),
+            momentum: -12.5,
+            solver: Solver::new(45, 0.25).unwrap(),
+        }
+    }
+
+    #[test]
+    fn version_0_descriptions_migrate() {
+        let description = json!({
+            "potential": "x^2",
+            "wavefunction": "exp(-x^2)",
+            "grid": { "start": -5, "end": 5, "resolution": 200 },
+            "momentum": 1,
+            "times": [0, 1],
+        });
+        let expected = Project {
+            potential: formula("x^2"),
+            wavefunction: formula("exp(-x^2)"),
+            grid: Grid::new(-5.0, 5.0, 200).unwrap(),
+            momentum: 1.0,
+            solver: Solver::default(),
+        };
+        assert_eq!(Project::from_value(description.clone()).unwrap(), expected);
+
+        let mut without_momentum = description.clone();
+        without_momentum.as_object_mut().unwrap().remove("momentum");
+        assert_eq!(Project::from_value(without_momentum).unwrap().momentum, 0.0);
+
+        let mut without_grid = description;
+        without_grid.as_object_mut().unwrap().remove("grid");
+        assert!(Project::from_value(without_grid).unwrap_err().contains("grid"));
+    }
+
+    #[test]
+    fn newer_versions_are_rejected() {
+        let mut value: Value = serde_json::from_str(&project().to_json()).unwrap();
+        assert_eq!(value["version"], json!(PROJECT_VERSION));
+        assert!(Project::from_value(value.clone()).is_ok());
+
+        value["version"] = json!(PROJECT_VERSION + 1);
+        assert!(Project::from_value(value.clone()).unwrap_err().contains("newer"));
+        value["version"] = json!("1");
+        assert_eq!(Project::from_value(value).unwrap_err(), "Invalid project version");
+    }
+
+    #[test]
+    fn saved_projects_load_unchanged() {
+        let project = project();
+        let path = std::env::temp_dir().join(format!("qsim-project-{}.json", std::process::id()));
+        project.save(&path).unwrap();
+        let loaded = Project::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), project);
+
+        assert!(Project::load(&path).unwrap_err().starts_with(&path.display().to_string()));
+    }
+}
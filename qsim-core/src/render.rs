@@ -0,0 +1,301 @@
+//! Animations of an experiment drawn without the GUI, as a GIF or numbered PNGs
+//!
+//! Frames are drawn with a fixed 256 colour palette, so both formats store the
+//! palette indices as they are: the background, the potential, the plain
+//! probability density and a colour wheel for the phase of ψ
+
+use crate::complex::Complex;
+use crate::experiment::Experiment;
+use crate::linear::Vector;
+use serde::{Deserialize, Serialize};
+use std::{
+    borrow::Cow,
+    f64::consts::PI,
+    fs,
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+const BACKGROUND: u8 = 0;
+const POTENTIAL: u8 = 1;
+const DENSITY: u8 = 2;
+const AXIS: u8 = 3;
+/// Palette entries from here on are hues, red at phase zero
+const FIRST_HUE: u8 = 4;
+const HUES: usize = 256 - FIRST_HUE as usize;
+/// Empty space above the tallest density and around the potential, as a fraction of the height
+const MARGIN: f64 = 0.05;
+
+/// How frames are drawn
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RenderSettings {
+    pub width: u32,
+    pub height: u32,
+    /// Colours |ψ|² by the phase of ψ instead of a single colour
+    pub phase_colors: bool,
+    /// Playback speed of GIFs
+    pub frames_per_second: u32,
+}
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self { width: 640, height: 360, phase_colors: true, frames_per_second: 25 }
+    }
+}
+
+/// A frame as indices into `palette()`, row by row from the top
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+/// RGB triples for every palette index
+pub fn palette() -> Vec<u8> {
+    let mut palette = vec![255, 255, 255, 40, 40, 40, 30, 90, 200, 160, 160, 160];
+    for k in 0..HUES {
+        let hue = k as f64 / HUES as f64;
+        palette.extend(hue_to_rgb(hue));
+    }
+    palette
+}
+
+fn hue_to_rgb(hue: f64) -> [u8; 3] {
+    let channel = |offset: f64| {
+        let h = (hue + offset).rem_euclid(1.0) * 6.0;
+        let value = (h - 3.0).abs() - 1.0;
+        (value.clamp(0.0, 1.0) * 255.0).round() as u8
+    };
+    [channel(0.0), channel(2.0 / 3.0), channel(1.0 / 3.0)]
+}
+
+/// Draws the frames of one experiment on shared scales, so the density doesn't
+/// jump around as the animation plays
+pub struct Renderer<'a> {
+    experiment: &'a Experiment,
+    settings: RenderSettings,
+    /// ψ at each of the times, evolved once for both the scale and the drawing
+    frames: Vec<Vector>,
+    density_max: f64,
+    potential_range: (f64, f64),
+}
+impl<'a> Renderer<'a> {
+    /// Scales the density to the tallest it gets at any of `times`
+    pub fn new(experiment: &'a Experiment, settings: RenderSettings, times: &[f64]) -> Result<Self, String> {
+        if settings.width == 0 || settings.height == 0 || settings.width > 4096 || settings.height > 4096 {
+            return Err("Frames must be between 1 and 4096 pixels wide and tall".into());
+        }
+        if settings.frames_per_second == 0 || settings.frames_per_second > 100 {
+            return Err("Animations play at 1 to 100 frames per second".into());
+        }
+
+        let tallest = |frame: &Vector| frame.iter().map(|value| value.modulus_squared()).fold(0.0, f64::max);
+        let frames = experiment.evolve_many(times)?;
+        let density_max = frames.iter().map(tallest).fold(tallest(experiment.wavefunction()), f64::max);
+        let potential = experiment.potential().iter().map(|value| value.real);
+        let potential_range = potential.fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), value| {
+            (low.min(value), high.max(value))
+        });
+
+        Ok(Self { experiment, settings, frames, density_max, potential_range })
+    }
+
+    /// The frames, in the order of the times they were created with
+    pub fn images(&self) -> impl Iterator<Item = Image> + '_ {
+        self.frames.iter().map(move |frame| self.draw(frame))
+    }
+
+    fn draw(&self, frame: &Vector) -> Image {
+        let (width, height) = (self.settings.width as usize, self.settings.height as usize);
+        let potential = self.experiment.potential();
+        let points = frame.len();
+        let mut pixels = vec![BACKGROUND; width * height];
+
+        // The grid point under each column of pixels
+        let point = |column: usize| (column * points / width).min(points - 1);
+        let baseline = self.row(0.0);
+
+        for column in 0..width {
+            let value = frame[point(column)];
+            let fraction = if self.density_max > 0.0 { value.modulus_squared() / self.density_max } else { 0.0 };
+            let color = if self.settings.phase_colors { phase_color(value) } else { DENSITY };
+            for row in self.row(fraction)..baseline {
+                pixels[row * width + column] = color;
+            }
+            pixels[baseline * width + column] = AXIS;
+        }
+
+        let (low, high) = self.potential_range;
+        let potential_row = |column: usize| {
+            let value = potential[point(column)].real;
+            let fraction = if high > low { (value - low) / (high - low) } else { 0.5 };
+            self.row(fraction)
+        };
+        for column in 0..width {
+            // Joins each column to the previous one so steep walls stay connected
+            let here = potential_row(column);
+            let previous = if column > 0 { potential_row(column - 1) } else { here };
+            for row in here.min(previous)..=here.max(previous) {
+                pixels[row * width + column] = POTENTIAL;
+                if row + 1 < height {
+                    pixels[(row + 1) * width + column] = POTENTIAL;
+                }
+            }
+        }
+
+        Image { width: self.settings.width, height: self.settings.height, pixels }
+    }
+
+    /// The pixel row for a fraction of the plot height, 0 at the bottom
+    fn row(&self, fraction: f64) -> usize {
+        let height = self.settings.height as f64;
+        let fraction = fraction.clamp(0.0, 1.0) * (1.0 - 2.0 * MARGIN) + MARGIN;
+        ((1.0 - fraction) * (height - 1.0)).round() as usize
+    }
+}
+
+fn phase_color(value: Complex) -> u8 {
+    let hue = value.angle_radians().rem_euclid(2.0 * PI) / (2.0 * PI);
+    FIRST_HUE + ((hue * HUES as f64) as usize % HUES) as u8
+}
+
+/// Renders the experiment at every time in `times` into a looping GIF
+pub fn write_gif(path: &Path, experiment: &Experiment, times: &[f64], settings: RenderSettings) -> Result<(), String> {
+    let renderer = Renderer::new(experiment, settings, times)?;
+    let error = |error: gif::EncodingError| format!("{}: {}", path.display(), error);
+    let file = fs::File::create(path).map_err(|error| format!("{}: {}", path.display(), error))?;
+    let mut encoder = gif::Encoder::new(
+        BufWriter::new(file),
+        settings.width as u16,
+        settings.height as u16,
+        &palette(),
+    )
+    .map_err(error)?;
+    encoder.set_repeat(gif::Repeat::Infinite).map_err(error)?;
+
+    // GIF delays are in hundredths of a second
+    let delay = (100 / settings.frames_per_second).max(1) as u16;
+    for image in renderer.images() {
+        let frame = gif::Frame {
+            width: settings.width as u16,
+            height: settings.height as u16,
+            buffer: Cow::Owned(image.pixels),
+            delay,
+            ..gif::Frame::default()
+        };
+        encoder.write_frame(&frame).map_err(error)?;
+    }
+    Ok(())
+}
+
+/// Renders the experiment at every time in `times` into `frame_0000.png`,
+/// `frame_0001.png`, ... inside `directory`, which is created if needed
+pub fn write_pngs(
+    directory: &Path,
+    experiment: &Experiment,
+    times: &[f64],
+    settings: RenderSettings,
+) -> Result<Vec<PathBuf>, String> {
+    let renderer = Renderer::new(experiment, settings, times)?;
+    fs::create_dir_all(directory).map_err(|error| format!("{}: {}", directory.display(), error))?;
+    let digits = times.len().saturating_sub(1).to_string().len().max(4);
+
+    let mut paths = Vec::with_capacity(times.len());
+    for (index, image) in renderer.images().enumerate() {
+        let path = directory.join(format!("frame_{:0digits$}.png", index, digits = digits));
+        write_png(&path, &image).map_err(|error| format!("{}: {}", path.display(), error))?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+pub fn write_png(path: &Path, image: &Image) -> Result<(), String> {
+    let file = fs::File::create(path).map_err(|error| error.to_string())?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), image.width, image.height);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_palette(palette());
+    let mut writer = encoder.write_header().map_err(|error| error.to_string())?;
+    writer.write_image_data(&image.pixels).map_err(|error| error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::experiment::{Grid, InitialState, Potential, Solver};
+
+    const SETTINGS: RenderSettings = RenderSettings { width: 48, height: 32, phase_colors: true, frames_per_second: 20 };
+    const TIMES: [f64; 3] = [0.0, 0.5, 3.0];
+
+    fn experiment() -> Experiment {
+        let potential = Potential::parse("x^2/10").unwrap();
+        let initial_state = InitialState::parse("e^(-(x+1)^2)").unwrap();
+        Solver::default().solve(Grid::new(-5.0, 5.0, 64).unwrap(), &potential, &initial_state, 2.0).unwrap()
+    }
+
+    #[test]
+    fn settings_are_checked() {
+        let experiment = experiment();
+        for settings in [
+            RenderSettings { width: 0, ..SETTINGS },
+            RenderSettings { height: 5000, ..SETTINGS },
+            RenderSettings { frames_per_second: 0, ..SETTINGS },
+        ] {
+            assert!(Renderer::new(&experiment, settings, &TIMES).is_err());
+        }
+    }
+
+    #[test]
+    fn gifs_hold_every_frame() {
+        let experiment = experiment();
+        let images: Vec<Image> = Renderer::new(&experiment, SETTINGS, &TIMES).unwrap().images().collect();
+        let path = std::env::temp_dir().join(format!("qsim-render-{}.gif", std::process::id()));
+        write_gif(&path, &experiment, &TIMES, SETTINGS).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::Indexed);
+        let mut decoder = options.read_info(bytes.as_slice()).unwrap();
+        assert_eq!((decoder.width(), decoder.height()), (48, 32));
+        assert_eq!(decoder.global_palette(), Some(palette().as_slice()));
+        let mut frames = 0;
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            assert_eq!((frame.width, frame.height, frame.delay), (48, 32, 5));
+            assert_eq!(frame.buffer.as_ref(), images[frames].pixels.as_slice());
+            frames += 1;
+        }
+        assert_eq!(frames, TIMES.len());
+        assert_ne!(images[0], images[2]);
+    }
+
+    #[test]
+    fn pngs_hold_one_frame_each() {
+        let experiment = experiment();
+        let images: Vec<Image> = Renderer::new(&experiment, SETTINGS, &TIMES).unwrap().images().collect();
+        let directory = std::env::temp_dir().join(format!("qsim-render-{}", std::process::id()));
+        let paths = write_pngs(&directory, &experiment, &TIMES, SETTINGS).unwrap();
+        let decoded: Vec<(PathBuf, png::Info<'static>, Vec<u8>)> = paths
+            .iter()
+            .map(|path| {
+                let mut decoder = png::Decoder::new(fs::File::open(path).unwrap());
+                decoder.set_transformations(png::Transformations::IDENTITY);
+                let mut reader = decoder.read_info().unwrap();
+                let mut pixels = vec![0; reader.output_buffer_size()];
+                reader.next_frame(&mut pixels).unwrap();
+                (path.clone(), reader.info().clone(), pixels)
+            })
+            .collect();
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(decoded.len(), TIMES.len());
+        for (index, ((path, info, pixels), image)) in decoded.iter().zip(&images).enumerate() {
+            assert_eq!(path.file_name().unwrap().to_str(), Some(format!("frame_{:04}.png", index).as_str()));
+            assert_eq!((info.width, info.height), (48, 32));
+            assert_eq!(info.color_type, png::ColorType::Indexed);
+            assert_eq!(info.palette.as_deref(), Some(palette().as_slice()));
+            assert_eq!(pixels, &image.pixels);
+        }
+    }
+}
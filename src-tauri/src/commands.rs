@@ -0,0 +1,573 @@
+//! What the Tauri commands do, taking the experiment by reference instead of
+//! through `tauri::State` so it can be driven without a window
+
+use qsim_core::{
+    export::{self, Format},
+    import::{self, Interpolation},
+    plot,
+    project::FunctionSource,
+    Complex, Datapoints, Experiment, Formula, FormulaError, Grid, InitialState, Potential, Project, Solver, Vector,
+};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// Assumed free memory where the system doesn't report it
+const FALLBACK_MEMORY: u64 = 1 << 30;
+
+#[derive(Default)]
+pub struct ExperimentState {
+    state: Mutex<Option<Experiment>>,
+}
+impl ExperimentState {
+    /// Every write replaces the whole experiment, so a command that panicked
+    /// halfway can't have left it inconsistent and the lock is recovered
+    fn lock(&self) -> MutexGuard<'_, Option<Experiment>> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+pub fn formula_error(formula: &str) -> Option<FormulaError> {
+    Formula::new(formula).err()
+}
+
+#[derive(Serialize)]
+pub struct RenderedFormula {
+    text: String,
+    latex: String,
+}
+
+/// Echoes the formula as it was parsed, so implicit multiplications become visible
+pub fn render_formula(formula: &str) -> Option<RenderedFormula> {
+    Formula::new(formula).ok().map(|formula| RenderedFormula {
+        text: formula.to_string(),
+        latex: formula.to_latex(),
+    })
+}
+
+/// Why a command failed, tagged by `kind` so the frontend can show the specific
+/// problem. `input` names what was wrong: "potential", "wavefunction", "momentum",
+/// "formula", "file" or "time"
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum QsimError {
+    Formula { input: &'static str, error: FormulaError },
+    /// Drawn or imported datapoints that can't be laid onto the grid
+    Datapoints { input: &'static str, message: String },
+    /// An infinity or NaN, at `x` when it comes from sampling a function
+    NotFinite { input: &'static str, x: Option<f64> },
+    Grid { message: String },
+    /// Nothing has been simulated since the parameters last changed
+    NoExperiment,
+    NoEigenstate { n: usize, count: usize },
+    Solver { message: String },
+    /// A file couldn't be read, written or parsed, `message` starts with its path
+    Io { message: String },
+    /// The file at `path` doesn't hold a project, even after migrating it
+    Project { path: String, message: String },
+}
+
+/// Grid bounds as sent by the frontend, checked by `Grid::new` when used
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct GridConfig {
+    start: f64,
+    end: f64,
+    resolution: u32,
+}
+impl GridConfig {
+    fn grid(&self) -> Result<Grid, QsimError> {
+        checked_grid(self.start, self.end, self.resolution).map_err(|message| QsimError::Grid { message })
+    }
+}
+
+/// `Grid::new`, also refusing resolutions whose eigenbasis wouldn't fit in memory
+/// and bounds the frontend can't plot
+fn checked_grid(start: f64, end: f64, resolution: u32) -> Result<Grid, String> {
+    if start.abs().max(end.abs()) > f32::MAX as f64 {
+        return Err("Grid bounds must fit in single precision".into());
+    }
+    let available = available_memory();
+    if simulation_bytes(resolution) > available {
+        let largest = (available as f64 / simulation_bytes(1) as f64).sqrt() as u64;
+        return Err(format!(
+            "A resolution of {} needs about {} MiB but only {} MiB are free, try at most {}",
+            resolution,
+            simulation_bytes(resolution) >> 20,
+            available >> 20,
+            largest
+        ));
+    }
+    Grid::new(start, end, resolution)
+}
+
+/// Peak bytes of a simulation: resolution² complex numbers for the eigenvectors,
+/// once per eigenpair and once in the contiguous basis, next to the basis of the
+/// experiment being replaced
+fn simulation_bytes(resolution: u32) -> u64 {
+    (resolution as u64).pow(2).saturating_mul(3 * 16)
+}
+
+/// Memory the system can still hand out, from `/proc/meminfo` where there is one
+fn available_memory() -> u64 {
+    fs::read_to_string("/proc/meminfo")
+        .ok()
+        .and_then(|meminfo| {
+            let line = meminfo.lines().find(|line| line.starts_with("MemAvailable:"))?;
+            let kibibytes: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+            Some(kibibytes * 1024)
+        })
+        .unwrap_or(FALLBACK_MEMORY)
+}
+
+/// Everything `simulate` needs, laid out like a project file
+#[derive(Debug, Clone, Deserialize)]
+pub struct SimulationConfig {
+    potential: FunctionSource,
+    wavefunction: FunctionSource,
+    grid: GridConfig,
+    #[serde(default)]
+    momentum: f64,
+    #[serde(default)]
+    solver: Solver,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SimulationSummary {
+    /// Number of eigenstates found, the valid `n` of `get_eigenvector`
+    eigenstates: usize,
+    /// Expected energy of the initial state, in the units of the eigenvalues
+    mean_energy: f64,
+}
+
+fn parse(formula: &str, input: &'static str) -> Result<Formula, QsimError> {
+    Formula::new(formula).map_err(|error| QsimError::Formula { input, error })
+}
+
+/// The function on the grid, from its formula or its datapoints
+fn sample(source: &FunctionSource, grid: &Grid, input: &'static str) -> Result<Vec<Complex>, QsimError> {
+    if source.use_formula {
+        evaluate(&parse(&source.formula, input)?, grid, input)
+    } else {
+        // `interpolate` only returns finite values
+        Ok(import::interpolate(&source.datapoints, grid, source.interpolation)
+            .map_err(|message| QsimError::Datapoints { input, message })?
+            .into_iter()
+            .map(Complex::from)
+            .collect())
+    }
+}
+
+/// Like `Formula::get_vector`, but a division by zero or an infinity is an
+/// error instead of being drawn as zero or breaking the plot
+fn evaluate(formula: &Formula, grid: &Grid, input: &'static str) -> Result<Vec<Complex>, QsimError> {
+    grid.positions()
+        .map(|x| match formula.evaluate_complex(Complex::from(x)) {
+            Ok(value) if value.real.is_finite() && value.imag.is_finite() => Ok(value),
+            _ => Err(QsimError::NotFinite { input, x: Some(x) }),
+        })
+        .collect()
+}
+
+pub fn compute_formula(formula: &str, grid: GridConfig, normalize: bool) -> Result<Datapoints, QsimError> {
+    let grid = grid.grid()?;
+    let formula = parse(formula, "formula")?;
+    let mut values = Vector::from(evaluate(&formula, &grid, "formula")?);
+
+    if normalize {
+        values.normalize();
+    }
+
+    plotted(&grid, &values, "formula")
+}
+
+/// The values on the grid as the frontend receives them, in single precision,
+/// which larger values would reach as infinities
+fn plotted(grid: &Grid, values: &Vector, input: &'static str) -> Result<Datapoints, QsimError> {
+    let unplottable = |value: &Complex| !value.real.is_finite() || value.real.abs() > f32::MAX as f64;
+    if let Some((x, _)) = grid.positions().zip(values.iter()).find(|(_, value)| unplottable(value)) {
+        return Err(QsimError::NotFinite { input, x: Some(x) });
+    }
+    Ok(Datapoints::on_grid(grid, values.iter().map(|value| value.real)))
+}
+
+/// The `n`th eigenstate of the current experiment, on its grid
+pub fn get_eigenvector(n: usize, state: &ExperimentState) -> Result<Datapoints, QsimError> {
+    let data = state.lock();
+    let experiment = data.as_ref().ok_or(QsimError::NoExperiment)?;
+    let count = experiment.basis().count();
+    let values = experiment.eigenvector(n).ok_or(QsimError::NoEigenstate { n, count })?;
+
+    Ok(Datapoints::on_grid(experiment.grid(), values.iter().map(|value| value.real)))
+}
+
+pub fn simulate(config: SimulationConfig, state: &ExperimentState) -> Result<SimulationSummary, QsimError> {
+    let grid = config.grid.grid()?;
+    let potential = sample(&config.potential, &grid, "potential")?;
+    let potential = Potential::Samples(potential.iter().map(|value| value.real).collect());
+    // The wavefunction's formula is handed over as such, since it is multiplied by the phase
+    // before sampling, and only evaluated here to catch infinities
+    let initial_state = if config.wavefunction.use_formula {
+        let formula = parse(&config.wavefunction.formula, "wavefunction")?;
+        evaluate(&formula, &grid, "wavefunction")?;
+        InitialState::Formula(formula)
+    } else {
+        let wavefunction = sample(&config.wavefunction, &grid, "wavefunction")?;
+        InitialState::Samples(grid.positions().zip(wavefunction.iter().map(|value| value.real)).collect())
+    };
+    if !config.momentum.is_finite() {
+        return Err(QsimError::NotFinite { input: "momentum", x: None });
+    }
+
+    let experiment = config
+        .solver
+        .solve(grid, &potential, &initial_state, config.momentum)
+        .map_err(|message| QsimError::Solver { message })?;
+    let mean_energy = experiment
+        .coefficients()
+        .iter()
+        .zip(experiment.eigenvalues())
+        .map(|(coefficient, energy)| coefficient.modulus_squared() * energy)
+        .sum();
+    let summary = SimulationSummary { eigenstates: experiment.basis().count(), mean_energy };
+
+    *state.lock() = Some(experiment);
+    Ok(summary)
+}
+
+pub fn restart(state: &ExperimentState) {
+    let mut data = state.lock();
+    *data = None;
+}
+
+/// The probability density of the current experiment at `time`, on its grid
+pub fn evolve(time: f64, state: &ExperimentState) -> Result<Datapoints, QsimError> {
+    if !time.is_finite() {
+        return Err(QsimError::NotFinite { input: "time", x: None });
+    }
+    let data = state.lock();
+    let experiment = data.as_ref().ok_or(QsimError::NoExperiment)?;
+    let frame = experiment.evolve(time).map_err(|message| QsimError::Solver { message })?;
+
+    Ok(probability_density(frame, experiment.grid()))
+}
+
+/// Like `evolve` for several times at once, so playback can buffer ahead
+pub fn evolve_frames(times: Vec<f64>, state: &ExperimentState) -> Result<Vec<Datapoints>, QsimError> {
+    if times.iter().any(|time| !time.is_finite()) {
+        return Err(QsimError::NotFinite { input: "time", x: None });
+    }
+    let data = state.lock();
+    let experiment = data.as_ref().ok_or(QsimError::NoExperiment)?;
+    let frames = experiment.evolve_many(&times).map_err(|message| QsimError::Solver { message })?;
+
+    Ok(frames.into_iter().map(|frame| probability_density(frame, experiment.grid())).collect())
+}
+
+pub fn save_project(path: String, project: Project) -> Result<(), QsimError> {
+    project.save(Path::new(&path)).map_err(|message| QsimError::Io { message })
+}
+
+/// Older project files are migrated on the way in
+pub fn load_project(path: String) -> Result<Project, QsimError> {
+    let json = fs::read_to_string(&path).map_err(|error| QsimError::Io { message: format!("{}: {}", path, error) })?;
+    Project::from_json(&json).map_err(|message| QsimError::Project { path, message })
+}
+
+/// Checks what the exports would otherwise fail on halfway, so only file errors remain
+fn check_export(experiment: &Experiment, eigenvectors: &[usize], times: &[f64]) -> Result<(), QsimError> {
+    let count = experiment.basis().count();
+    if let Some(&n) = eigenvectors.iter().find(|&&n| n >= count) {
+        return Err(QsimError::NoEigenstate { n, count });
+    }
+    if times.iter().any(|time| !time.is_finite()) {
+        return Err(QsimError::NotFinite { input: "time", x: None });
+    }
+    Ok(())
+}
+
+/// Writes the potential, the eigenvalues, the chosen eigenvectors and the frames
+/// at `times` of the current experiment into `directory`
+pub fn export_results(
+    directory: String,
+    format: Format,
+    eigenvectors: Vec<usize>,
+    times: Vec<f64>,
+    state: &ExperimentState,
+) -> Result<(), QsimError> {
+    let data = state.lock();
+    let experiment = data.as_ref().ok_or(QsimError::NoExperiment)?;
+    check_export(experiment, &eigenvectors, &times)?;
+    export::export(experiment, Path::new(&directory), format, &eigenvectors, &times)
+        .map(|_| ())
+        .map_err(|message| QsimError::Io { message })
+}
+
+/// Writes SVG figures of the potential, the chosen eigenstates and the density
+/// at `times` of the current experiment into `directory`
+pub fn export_plots(
+    directory: String,
+    eigenvectors: Vec<usize>,
+    times: Vec<f64>,
+    state: &ExperimentState,
+) -> Result<(), QsimError> {
+    let data = state.lock();
+    let experiment = data.as_ref().ok_or(QsimError::NoExperiment)?;
+    check_export(experiment, &eigenvectors, &times)?;
+    plot::save_figures(experiment, Path::new(&directory), &eigenvectors, &times)
+        .map(|_| ())
+        .map_err(|message| QsimError::Io { message })
+}
+
+/// Reads `(x, y)` columns from a data file and interpolates them onto the grid,
+/// to be edited further as a drawing
+pub fn import_datapoints(
+    path: String,
+    grid: GridConfig,
+    interpolation: Interpolation,
+    normalize: bool,
+) -> Result<Datapoints, QsimError> {
+    let grid = grid.grid()?;
+    let points = import::read(Path::new(&path)).map_err(|message| QsimError::Io { message })?;
+    let values = import::interpolate(&points, &grid, interpolation)
+        .map_err(|message| QsimError::Datapoints { input: "file", message })?;
+    let mut values = Vector::from(values.into_iter().map(Complex::from).collect::<Vec<Complex>>());
+
+    if normalize {
+        values.normalize();
+    }
+
+    plotted(&grid, &values, "file")
+}
+
+fn probability_density(frame: Vector, grid: &Grid) -> Datapoints {
+    Datapoints::on_grid(grid, frame.iter().map(|value| value.modulus_squared()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use std::panic::{self, AssertUnwindSafe};
+
+    /// Well formed formulas, random pieces of them and arbitrary text
+    fn formula() -> impl Strategy<Value = String> {
+        let leaf = prop_oneof![Just("x"), Just("i"), Just("pi"), Just("2"), Just("0"), Just("1e300")].prop_map(String::from);
+        let well_formed = leaf.prop_recursive(4, 16, 2, |inner| {
+            let operator = prop_oneof![Just("+"), Just("-"), Just("*"), Just("/"), Just("^"), Just(" ")];
+            let function = prop_oneof![Just("sin"), Just("sqrt"), Just("exp"), Just("ln"), Just("abs"), Just("step")];
+            prop_oneof![
+                (inner.clone(), operator, inner.clone()).prop_map(|(a, operator, b)| format!("({}{}{})", a, operator, b)),
+                (function, inner).prop_map(|(function, a)| format!("{}({})", function, a)),
+            ]
+        });
+        let piece = prop_oneof![
+            Just("x"), Just("i"), Just("e"), Just("pi"), Just("2"), Just("0"), Just("1e300"), Just(".5"),
+            Just("+"), Just("-"), Just("*"), Just("/"), Just("^"), Just("("), Just(")"), Just(" "),
+            Just("sin"), Just("sqrt"), Just("exp"), Just("ln"), Just("abs"), Just("step"), Just("y"), Just("é"),
+        ];
+        prop_oneof![
+            3 => well_formed,
+            1 => prop::collection::vec(piece, 0..40).prop_map(|pieces| pieces.concat()),
+            1 => any::<String>(),
+        ]
+    }
+
+    fn number() -> impl Strategy<Value = f64> {
+        prop_oneof![
+            -10.0..10.0f64,
+            Just(0.0),
+            Just(f64::NAN),
+            Just(f64::INFINITY),
+            Just(f64::NEG_INFINITY),
+            Just(f64::MAX),
+            Just(f64::MIN_POSITIVE),
+            any::<f64>(),
+        ]
+    }
+
+    /// Mostly valid grids, so the commands get past checking them
+    fn grid(max_resolution: u32) -> impl Strategy<Value = GridConfig> {
+        let valid = (-10.0..-0.1f64, 0.1..10.0f64, 1..=max_resolution);
+        let resolution = prop_oneof![0..=max_resolution, Just(u32::MAX)];
+        prop_oneof![
+            3 => valid.prop_map(|(start, end, resolution)| GridConfig { start, end, resolution }),
+            1 => (number(), number(), resolution).prop_map(|(start, end, resolution)| GridConfig { start, end, resolution }),
+        ]
+    }
+
+    /// Mostly points evenly spaced across the grid, so they can be interpolated on it
+    fn points(grid: GridConfig) -> impl Strategy<Value = Vec<(f64, f64)>> {
+        let evenly_spaced = prop::collection::vec(-10.0..10.0f64, 2..20).prop_map(move |ys| {
+            let step = (grid.end - grid.start) / (ys.len() - 1) as f64;
+            ys.iter().enumerate().map(|(i, y)| (grid.start + i as f64 * step, *y)).collect()
+        });
+        prop_oneof![
+            3 => evenly_spaced,
+            1 => prop::collection::vec((number(), number()), 0..20),
+        ]
+    }
+
+    fn source(grid: GridConfig) -> impl Strategy<Value = FunctionSource> {
+        (formula(), points(grid), any::<bool>()).prop_map(|(formula, datapoints, use_formula)| FunctionSource {
+            formula,
+            datapoints,
+            use_formula,
+            interpolation: Interpolation::default(),
+        })
+    }
+
+    /// Times whose phases overflow even the smallest energies here
+    fn overflows(time: f64) -> bool {
+        !time.is_finite() || time.abs() > 1e300
+    }
+
+    fn assert_finite(datapoints: &Datapoints) {
+        assert!(datapoints.values.iter().all(|point| point.x.is_finite() && point.y.is_finite()));
+    }
+
+    /// An experiment that always simulates, to evolve and query
+    fn simulated() -> ExperimentState {
+        let state = ExperimentState::default();
+        let formula = |formula: &str| FunctionSource {
+            formula: formula.into(),
+            datapoints: vec![],
+            use_formula: true,
+            interpolation: Interpolation::default(),
+        };
+        let config = SimulationConfig {
+            potential: formula("x^2"),
+            wavefunction: formula("exp(-x^2)"),
+            grid: GridConfig { start: -5.0, end: 5.0, resolution: 32 },
+            momentum: 1.0,
+            solver: Solver::default(),
+        };
+        simulate(config, &state).unwrap();
+        state
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(512))]
+
+        #[test]
+        fn formula_commands_never_panic(formula in formula(), grid in grid(256), normalize in any::<bool>()) {
+            let valid = formula_error(&formula).is_none();
+            prop_assert_eq!(render_formula(&formula).is_some(), valid);
+            match compute_formula(&formula, grid, normalize) {
+                Ok(datapoints) => {
+                    prop_assert!(valid);
+                    prop_assert_eq!(datapoints.values.len(), grid.resolution as usize);
+                    assert_finite(&datapoints);
+                }
+                Err(QsimError::Formula { .. }) => prop_assert!(!valid),
+                Err(QsimError::Grid { .. } | QsimError::NotFinite { .. }) => {}
+                Err(error) => prop_assert!(false, "unexpected {:?}", error),
+            }
+        }
+
+        #[test]
+        fn simulate_never_panics(
+            (grid, potential, wavefunction) in grid(24).prop_flat_map(|grid| (Just(grid), source(grid), source(grid))),
+            momentum in number(),
+            n in any::<usize>(),
+            time in number(),
+        ) {
+            let state = ExperimentState::default();
+            let config = SimulationConfig { potential, wavefunction, grid, momentum, solver: Solver::default() };
+            match simulate(config, &state) {
+                Ok(summary) => {
+                    prop_assert!(summary.mean_energy.is_finite());
+                    prop_assert_eq!(get_eigenvector(n, &state).is_ok(), n < summary.eigenstates);
+                    match evolve(time, &state) {
+                        Ok(frame) => assert_finite(&frame),
+                        Err(error) => prop_assert!(overflows(time), "unexpected {:?}", error),
+                    }
+                }
+                Err(_) => {
+                    prop_assert!(matches!(get_eigenvector(n, &state), Err(QsimError::NoExperiment)));
+                    match evolve(time, &state) {
+                        Err(QsimError::NoExperiment) => {}
+                        Err(QsimError::NotFinite { .. }) => prop_assert!(!time.is_finite()),
+                        result => prop_assert!(false, "unexpected {:?}", result),
+                    }
+                }
+            }
+        }
+
+        #[test]
+        fn evolving_never_panics(n in any::<usize>(), times in prop::collection::vec(number(), 0..10)) {
+            let state = simulated();
+            let count = 32;
+            match get_eigenvector(n, &state) {
+                Ok(datapoints) => assert_finite(&datapoints),
+                Err(QsimError::NoEigenstate { n: missing, count: available }) => {
+                    prop_assert_eq!((missing, available), (n, count));
+                    prop_assert!(n >= count);
+                }
+                Err(error) => prop_assert!(false, "unexpected {:?}", error),
+            }
+            match evolve_frames(times.clone(), &state) {
+                Ok(frames) => {
+                    prop_assert_eq!(frames.len(), times.len());
+                    frames.iter().for_each(assert_finite);
+                }
+                Err(error) => prop_assert!(times.iter().any(|time| overflows(*time)), "unexpected {:?}", error),
+            }
+        }
+
+        #[test]
+        fn imported_datapoints_never_panic(
+            (grid, points) in grid(256).prop_flat_map(|grid| (Just(grid), points(grid))),
+            normalize in any::<bool>(),
+        ) {
+            let path = std::env::temp_dir().join(format!("qsim-import-{:?}.csv", std::thread::current().id()));
+            let text: String = points.iter().map(|(x, y)| format!("{},{}\n", x, y)).collect();
+            fs::write(&path, text).unwrap();
+            let result = import_datapoints(path.to_string_lossy().into(), grid, Interpolation::default(), normalize);
+            fs::remove_file(&path).unwrap();
+            match result {
+                Ok(datapoints) => assert_finite(&datapoints),
+                Err(
+                    QsimError::Grid { .. }
+                    | QsimError::Io { .. }
+                    | QsimError::Datapoints { .. }
+                    | QsimError::NotFinite { .. },
+                ) => {}
+                Err(error) => prop_assert!(false, "unexpected {:?}", error),
+            }
+        }
+    }
+
+    #[test]
+    fn commands_need_an_experiment() {
+        let state = simulated();
+        restart(&state);
+        assert!(matches!(get_eigenvector(0, &state), Err(QsimError::NoExperiment)));
+        assert!(matches!(evolve(0.0, &state), Err(QsimError::NoExperiment)));
+        assert!(matches!(evolve_frames(vec![0.0], &state), Err(QsimError::NoExperiment)));
+        let directory = std::env::temp_dir().to_string_lossy().into_owned();
+        assert!(matches!(
+            export_results(directory.clone(), Format::Csv, vec![], vec![], &state),
+            Err(QsimError::NoExperiment)
+        ));
+        assert!(matches!(export_plots(directory, vec![], vec![], &state), Err(QsimError::NoExperiment)));
+    }
+
+    #[test]
+    fn a_poisoned_lock_is_recovered() {
+        let state = simulated();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let _guard = state.lock();
+            panic!("a command panicked");
+        }));
+        assert!(result.is_err());
+        assert!(evolve(1.0, &state).is_ok());
+    }
+
+    #[test]
+    fn resolutions_beyond_memory_are_refused() {
+        let grid = GridConfig { start: 0.0, end: 1.0, resolution: u32::MAX };
+        assert!(matches!(compute_formula("x", grid, false), Err(QsimError::Grid { .. })));
+        assert!(simulation_bytes(u32::MAX) > available_memory());
+    }
+}
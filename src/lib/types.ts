@@ -7,7 +7,7 @@ export interface Function {
     mode: FunctionMode,
     formula: string,
     sketching: boolean,
-    formula_error: string,
+    formula_error: FormulaError | null,
     datapoints: Datapoints | null,
     show_mean: boolean,
     scale: {
@@ -19,6 +19,101 @@ export interface Function {
     readonly: boolean,
     n: number | null,
 }
+// Mirrors formula::FormulaError, spans are byte offsets into the formula
+export interface FormulaError {
+    kind: string,
+    span: {
+        start: number,
+        end: number,
+    },
+    expected: string[],
+    suggestions: string[],
+    message: string,
+}
+// Text for the error tooltip, with what was expected and any suggested fixes
+export function describe_formula_error(error: FormulaError): string {
+    let description = error.message
+    if (error.expected.length > 0) {
+        description += `, expected ${error.expected.join(" or ")}`
+    }
+    if (error.suggestions.length > 0) {
+        description += `, did you mean ${error.suggestions.map(s => `\`${s}\``).join(" or ")}?`
+    }
+    return description
+}
+// Mirrors QsimError of the Tauri commands, tagged by kind
+export type QsimError =
+    | { kind: 'formula', input: string, error: FormulaError }
+    | { kind: 'datapoints', input: string, message: string }
+    | { kind: 'not_finite', input: string, x: number | null }
+    | { kind: 'grid', message: string }
+    | { kind: 'no_experiment' }
+    | { kind: 'no_eigenstate', n: number, count: number }
+    | { kind: 'solver', message: string }
+    | { kind: 'io', message: string }
+    | { kind: 'project', path: string, message: string }
+export function describe_error(error: QsimError): string {
+    switch (error.kind) {
+        case 'formula':
+            return `The ${error.input} formula is invalid: ${describe_formula_error(error.error)}`
+        case 'datapoints':
+            return `The ${error.input} datapoints don't fit the grid: ${error.message}`
+        case 'not_finite':
+            return error.x === null
+                ? `The ${error.input} is not a finite number`
+                : `The ${error.input} is infinite or undefined at x = ${error.x.toPrecision(4)}`
+        case 'grid':
+            return error.message
+        case 'no_experiment':
+            return 'Nothing has been simulated yet'
+        case 'no_eigenstate':
+            return `There are only ${error.count} eigenstates, so there is no n = ${error.n}`
+        case 'solver':
+            return `The solver failed: ${error.message}`
+        case 'io':
+            return error.message
+        case 'project':
+            return `${error.path} is not a valid project: ${error.message}`
+    }
+}
+// Mirrors SimulationSummary, returned by simulate
+export interface SimulationSummary {
+    eigenstates: number,
+    mean_energy: number,
+}
+// Mirrors qsim_core::Solver
+export interface Solver {
+    iterations: number,
+    kept_fraction: number,
+}
+// Mirrors qsim_core::import::Interpolation
+export type Interpolation = 'linear' | 'cubic_spline' | 'monotone'
+// Mirrors qsim_core::Project, datapoints are [x, y] pairs
+export interface FunctionSource {
+    formula: string,
+    datapoints: [number, number][],
+    use_formula: boolean,
+    interpolation?: Interpolation,
+}
+// The source of a function as stored in projects and sent to simulate
+export function to_source(fn: Function): FunctionSource {
+    return {
+        formula: fn.formula,
+        datapoints: fn.datapoints ? fn.datapoints.values.map(d => [d.x, d.y]) : [],
+        use_formula: fn.mode === FunctionMode.Formula,
+    }
+}
+export interface Project {
+    potential: FunctionSource,
+    wavefunction: FunctionSource,
+    grid: {
+        start: number,
+        end: number,
+        resolution: number,
+    },
+    momentum: number,
+    solver: Solver,
+}
 export interface Bounds {
     left: number,
     right: number,